@@ -2,15 +2,23 @@
 //!
 //! Notifications with support multiple subscribers
 
-use crate::common::StaticTypeMap;
-use tokio::sync::broadcast::Sender;
+use crate::{
+    common::{StaticTypeMap, UntypedBox},
+    layer::{self, Kind, Stage},
+};
+use std::{any::TypeId, collections::HashMap};
+use tokio::sync::broadcast::{self, error::SendError, Sender};
 
+mod schedule;
 mod subscription;
+mod topic;
 
 #[cfg(test)]
 mod test;
 
+pub use schedule::*;
 pub use subscription::*;
+pub use topic::*;
 
 static CHANNELS: StaticTypeMap = StaticTypeMap::new();
 
@@ -26,17 +34,121 @@ pub trait Broadcast: Sized + 'static {
     const DEBUG_NAME: &'static str;
 
     /// Payload data type that will be sended with this notification
-    type Payload: Clone + Send;
+    type Payload: Clone + Send + 'static;
 }
 
 /// Sends a payload to the [Subscription](crate::broadcast::Subscription)
-pub async fn notify<B: Broadcast>(payload: B::Payload) {
+///
+/// The payload rejected by a [Layer](crate::layer::Layer) is dropped
+pub async fn notify<B: Broadcast>(mut payload: B::Payload) {
+    if !intercept::<B>(Stage::Send, None, &mut payload) {
+        return;
+    }
     let id = id!(B);
     let channels = CHANNELS.read().await;
-    let sender = match channels.get(&id) {
-        Some(sender) => sender,
+    let channel = match channels.get(&id) {
+        Some(channel) => channel,
         None => return,
     };
-    let sender: &Sender<_> = unsafe { sender.get_ref() };
-    let _ = sender.send(payload);
+    let channel: &BroadcastChannel<B> = unsafe { channel.get_ref() };
+    let _ = channel.sender.send(payload);
+}
+
+/// Sends the payloads to the [Subscription](crate::broadcast::Subscription)
+/// with one lookup of the subscriptions
+///
+/// The payloads rejected by a [Layer](crate::layer::Layer) are dropped
+///
+/// Returns the number of the sent payloads
+pub async fn notify_all<B, I>(payloads: I) -> Result<usize, NotifyAllError<B>>
+where
+    B: Broadcast,
+    I: IntoIterator<Item = B::Payload>,
+{
+    let mut payloads = payloads.into_iter();
+    let channels = CHANNELS.read().await;
+    let channel: &BroadcastChannel<B> = match channels.get(&id!(B)) {
+        Some(channel) => unsafe { channel.get_ref() },
+        None => {
+            let rest: Vec<_> = payloads.collect();
+            if rest.is_empty() {
+                return Ok(0);
+            }
+            return Err(NotifyAllError { accepted: 0, rest });
+        }
+    };
+    let mut accepted = 0;
+    while let Some(mut payload) = payloads.next() {
+        if !intercept::<B>(Stage::Send, None, &mut payload) {
+            continue;
+        }
+        if let Err(SendError(payload)) = channel.sender.send(payload) {
+            return Err(NotifyAllError {
+                accepted,
+                rest: std::iter::once(payload).chain(payloads).collect(),
+            });
+        }
+        accepted += 1;
+    }
+    Ok(accepted)
+}
+
+/// Error of the [notify_all](crate::broadcast::notify_all) fn,
+/// the broadcast has no subscriptions
+pub struct NotifyAllError<B: Broadcast> {
+    /// The number of the payloads sent before the failure
+    pub accepted: usize,
+    /// The payloads that are not sent
+    pub rest: Vec<B::Payload>,
+}
+
+impl<B: Broadcast> std::fmt::Debug for NotifyAllError<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "NotifyAllError in {}: NotSubscribed after {} accepted",
+            B::DEBUG_NAME,
+            self.accepted
+        )
+    }
+}
+
+/// Passes the payload through the [layers](crate::layer),
+/// returns false if it is rejected
+fn intercept<B: Broadcast>(stage: Stage, topic: Option<&str>, payload: &mut B::Payload) -> bool {
+    layer::apply::<B, _>(Kind::Broadcast, stage, B::DEBUG_NAME, topic, payload).is_ok()
+}
+
+/// Channels of the broadcast stored in the registry
+struct BroadcastChannel<B: Broadcast> {
+    sender: Sender<B::Payload>,
+    topics: TopicNode<B::Payload>,
+}
+
+impl<B: Broadcast> BroadcastChannel<B> {
+    fn new() -> Self {
+        let (sender, _) = broadcast::channel(B::BUFFER_SIZE);
+        Self {
+            sender,
+            topics: TopicNode::new(),
+        }
+    }
+
+    fn get_or_insert(channels: &mut HashMap<TypeId, UntypedBox>) -> &mut Self {
+        let channel = channels
+            .entry(id!(B))
+            .or_insert_with(|| UntypedBox::new(Self::new()));
+        unsafe { channel.get_mut() }
+    }
+
+    /// Removes the broadcast from the registry if it has no subscribers
+    fn remove_unused(channels: &mut HashMap<TypeId, UntypedBox>) {
+        let id = id!(B);
+        if let Some(channel) = channels.get(&id) {
+            let channel: &Self = unsafe { channel.get_ref() };
+            if channel.sender.receiver_count() == 0 && channel.topics.is_empty() {
+                channels.remove(&id);
+            }
+        }
+    }
 }
@@ -0,0 +1,72 @@
+use super::{notify, Broadcast};
+use crate::schedule::{schedule, ScheduleHandle};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Sends a payload to the [Subscription](super::Subscription) at the instant
+///
+/// The payload is sent by the [notify]
+/// on the current runtime, the schedule stops when that runtime is shut down
+///
+/// # Panics
+///
+/// Panics if called outside of the tokio runtime
+pub fn notify_at<B: Broadcast>(at: Instant, payload: B::Payload) -> ScheduleHandle {
+    let mut payload = Some(payload);
+    schedule(
+        at,
+        None,
+        Box::new(move || {
+            let payload = payload.take();
+            Box::pin(async move {
+                if let Some(payload) = payload {
+                    notify::<B>(payload).await;
+                }
+            })
+        }),
+    )
+}
+
+/// Sends a payload to the [Subscription](super::Subscription) after the delay
+///
+/// The payload is sent by the [notify]
+/// on the current runtime, the schedule stops when that runtime is shut down
+///
+/// # Panics
+///
+/// Panics if called outside of the tokio runtime
+pub fn notify_after<B: Broadcast>(delay: Duration, payload: B::Payload) -> ScheduleHandle {
+    notify_at::<B>(Instant::now() + delay, payload)
+}
+
+/// Sends a payload made by `make_payload`
+/// to the [Subscription](super::Subscription) every period
+/// until the schedule is cancelled
+///
+/// The first payload is sent after the period,
+/// the payloads are sent by the [notify]
+/// on the current runtime, the schedule stops when that runtime is shut down
+///
+/// The payloads are sent one at a time in order,
+/// the next one waits for the slow [notify] of the previous one
+///
+/// # Panics
+///
+/// Panics if `period` is zero or if called outside of the tokio runtime
+pub fn notify_every<B, F>(period: Duration, mut make_payload: F) -> ScheduleHandle
+where
+    B: Broadcast,
+    F: FnMut() -> B::Payload + Send + 'static,
+{
+    assert!(!period.is_zero(), "notify_every period must be non-zero");
+    schedule(
+        Instant::now() + period,
+        Some(period),
+        Box::new(move || {
+            let payload = make_payload();
+            Box::pin(async move {
+                notify::<B>(payload).await;
+            })
+        }),
+    )
+}
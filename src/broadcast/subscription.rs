@@ -1,6 +1,13 @@
-use super::{Broadcast, CHANNELS};
-use crate::common::UntypedBox;
-use tokio::sync::broadcast::{channel, error::RecvError, Receiver, Sender};
+use super::{intercept, Broadcast, BroadcastChannel, CHANNELS};
+use crate::{
+    layer::Stage,
+    rate::{Debounce, Sample, Throttle},
+};
+use std::time::Duration;
+use tokio::sync::broadcast::{
+    error::{RecvError, TryRecvError},
+    Receiver,
+};
 
 /// Broadcast notification subscription
 pub struct Subscription<B: Broadcast> {
@@ -9,22 +16,16 @@ pub struct Subscription<B: Broadcast> {
 
 /// Subscribe to broadcast notification
 pub async fn subscribe<B: Broadcast>() -> Subscription<B> {
-    let id = id!(B);
     let mut channels = CHANNELS.write().await;
-    let rx = if let Some(channel) = channels.get(&id) {
-        let channel = unsafe { channel.get_ref::<Sender<B::Payload>>() };
-        channel.subscribe()
-    } else {
-        let (tx, rx) = channel(B::BUFFER_SIZE);
-        let channel = UntypedBox::new(tx);
-        channels.insert(id, channel);
-        rx
-    };
+    let channel = BroadcastChannel::<B>::get_or_insert(&mut channels);
+    let rx = channel.sender.subscribe();
     Subscription { receiver: Some(rx) }
 }
 
 impl<B: Broadcast> Subscription<B> {
     /// Receives the next value for this Subscription
+    ///
+    /// Notifications rejected by a [Layer](crate::layer::Layer) are skipped
     pub async fn recv(&mut self) -> B::Payload {
         let receiver = match &mut self.receiver {
             Some(receiver) => receiver,
@@ -32,34 +33,83 @@ impl<B: Broadcast> Subscription<B> {
         };
         loop {
             match receiver.recv().await {
-                Ok(payload) => return payload,
+                Ok(mut payload) => {
+                    if intercept::<B>(Stage::Receive, None, &mut payload) {
+                        return payload;
+                    }
+                }
                 Err(RecvError::Closed) => unreachable!(),
                 Err(RecvError::Lagged(_)) => {}
             }
         }
     }
 
+    /// Receives up to `max` values for this Subscription into the `buffer`
+    ///
+    /// Waits for at least one value, then takes the buffered ones
+    /// without waiting
+    ///
+    /// Returns the number of the received values, 0 if `max` is 0
+    pub async fn recv_many(&mut self, buffer: &mut Vec<B::Payload>, max: usize) -> usize {
+        if max == 0 {
+            return 0;
+        }
+        buffer.push(self.recv().await);
+        let receiver = match &mut self.receiver {
+            Some(receiver) => receiver,
+            None => unreachable!(),
+        };
+        let mut count = 1;
+        while count < max {
+            match receiver.try_recv() {
+                Ok(mut payload) => {
+                    if intercept::<B>(Stage::Receive, None, &mut payload) {
+                        buffer.push(payload);
+                        count += 1;
+                    }
+                }
+                Err(TryRecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+        count
+    }
+
+    /// Adapts the subscription to receive a payload
+    /// only after no newer payloads are sent for the quiet `period`
+    pub fn debounce(self, period: Duration) -> Debounce<Self> {
+        Debounce::new(self, period)
+    }
+
+    /// Adapts the subscription to receive at most one payload per `period`
+    pub fn throttle(self, period: Duration) -> Throttle<Self> {
+        Throttle::new(self, period)
+    }
+
+    /// Adapts the subscription to receive the latest payload once per `period`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero
+    pub fn sample(self, period: Duration) -> Sample<Self> {
+        Sample::new(self, period)
+    }
+
     /// Closes the subscription
     ///
     /// Closing the subscription with this method
     /// is preferable for performance reasons
     pub async fn close(mut self) {
         drop(self.receiver.take());
-        let id = id!(B);
         let mut channels = CHANNELS.write().await;
-        if let Some(channel) = channels.get(&id) {
-            let channel = unsafe { channel.get_ref::<Sender<B::Payload>>() };
-            if channel.receiver_count() == 0 {
-                channels.remove(&id);
-            }
-        }
+        BroadcastChannel::<B>::remove_unused(&mut channels);
     }
 }
 
 impl<B: Broadcast> Drop for Subscription<B> {
     fn drop(&mut self) {
         if self.receiver.is_some() {
-            CHANNELS.remove_when_possible(id!(B));
+            CHANNELS.update_when_possible(BroadcastChannel::<B>::remove_unused);
             #[cfg(debug_assertions)]
             eprintln!(
                 "Subscription for {} closed in slow manner! Use close method for optimize it",
@@ -53,3 +53,128 @@ async fn many_subscribers() {
 
     assert!(CHANNELS.read().await.get(&id!(Broadcast1)).is_none());
 }
+
+#[derive(Clone)]
+struct PriceUpdate(i32);
+
+struct Prices;
+
+impl Broadcast for Prices {
+    type Payload = PriceUpdate;
+    const BUFFER_SIZE: usize = 8;
+    const DEBUG_NAME: &'static str = "Prices";
+}
+
+#[tokio::test]
+async fn topic_subscribers() {
+    println!("topic_subscribers: Subscribe to topics");
+    let mut exact = subscribe_topic::<Prices>("eq.NASDAQ.AAPL").await.unwrap();
+    let mut single = subscribe_topic::<Prices>("eq.NASDAQ.*").await.unwrap();
+    let mut multi = subscribe_topic::<Prices>("eq.#").await.unwrap();
+    let mut plain = subscribe::<Prices>().await;
+    match subscribe_topic::<Prices>("eq.#.AAPL").await {
+        Err(invalid) => assert_eq!(invalid.filter, "eq.#.AAPL"),
+        Ok(_) => panic!("Unexpected subscription"),
+    }
+
+    println!("topic_subscribers: Notify topics");
+    notify_topic::<Prices>("eq.NASDAQ.AAPL", PriceUpdate(1)).await;
+    notify_topic::<Prices>("eq.NYSE.IBM", PriceUpdate(2)).await;
+    notify_topic::<Prices>("eq.NASDAQ.MSFT", PriceUpdate(3)).await;
+    notify_topic::<Prices>("fx.EURUSD", PriceUpdate(4)).await;
+    notify::<Prices>(PriceUpdate(5)).await;
+
+    let (topic, PriceUpdate(price)) = exact.recv().await;
+    assert_eq!((topic.as_str(), price), ("eq.NASDAQ.AAPL", 1));
+    for expected in [("eq.NASDAQ.AAPL", 1), ("eq.NASDAQ.MSFT", 3)] {
+        let (topic, PriceUpdate(price)) = single.recv().await;
+        assert_eq!((topic.as_str(), price), expected);
+    }
+    for expected in [
+        ("eq.NASDAQ.AAPL", 1),
+        ("eq.NYSE.IBM", 2),
+        ("eq.NASDAQ.MSFT", 3),
+    ] {
+        let (topic, PriceUpdate(price)) = multi.recv().await;
+        assert_eq!((topic.as_str(), price), expected);
+    }
+    assert_eq!(plain.recv().await.0, 5);
+
+    println!("topic_subscribers: Wildcard topics are dropped");
+    notify_topic::<Prices>("eq.#", PriceUpdate(6)).await;
+    notify_topic::<Prices>("eq.*.AAPL", PriceUpdate(6)).await;
+    notify_topic::<Prices>("eq.NYSE.IBM", PriceUpdate(7)).await;
+    let (topic, PriceUpdate(price)) = multi.recv().await;
+    assert_eq!((topic.as_str(), price), ("eq.NYSE.IBM", 7));
+
+    println!("topic_subscribers: Close subscriptions");
+    exact.close().await;
+    single.close().await;
+    multi.close().await;
+    assert!(CHANNELS.read().await.get(&id!(Prices)).is_some());
+    plain.close().await;
+    assert!(CHANNELS.read().await.get(&id!(Prices)).is_none());
+}
+
+struct Batched;
+
+impl Broadcast for Batched {
+    type Payload = i32;
+    const BUFFER_SIZE: usize = 8;
+    const DEBUG_NAME: &'static str = "Batched";
+}
+
+#[tokio::test]
+async fn batch_receive() {
+    println!("batch_receive: Subscribe");
+    let mut subscription = subscribe::<Batched>().await;
+    let mut topics = subscribe_topic::<Batched>("batch.*").await.unwrap();
+
+    println!("batch_receive: Receive batches");
+    for n in 1..=5 {
+        notify::<Batched>(n).await;
+    }
+    let mut buffer = Vec::new();
+    assert_eq!(subscription.recv_many(&mut buffer, 3).await, 3);
+    assert_eq!(subscription.recv_many(&mut buffer, 3).await, 2);
+    assert_eq!(subscription.recv_many(&mut buffer, 0).await, 0);
+    assert_eq!(buffer, [1, 2, 3, 4, 5]);
+
+    println!("batch_receive: Receive topic batches");
+    notify_topic::<Batched>("batch.a", 1).await;
+    notify_topic::<Batched>("batch.b", 2).await;
+    let mut buffer = Vec::new();
+    assert_eq!(topics.recv_many(&mut buffer, 8).await, 2);
+    assert_eq!(
+        buffer,
+        [("batch.a".to_string(), 1), ("batch.b".to_string(), 2)]
+    );
+
+    subscription.close().await;
+    topics.close().await;
+}
+
+struct BatchedSend;
+
+impl Broadcast for BatchedSend {
+    type Payload = i32;
+    const BUFFER_SIZE: usize = 8;
+    const DEBUG_NAME: &'static str = "BatchedSend";
+}
+
+#[tokio::test]
+async fn batch_send() {
+    println!("batch_send: Not subscribed");
+    let error = notify_all::<BatchedSend, _>([1, 2]).await.unwrap_err();
+    assert_eq!(error.accepted, 0);
+    assert_eq!(error.rest, [1, 2]);
+    assert_eq!(notify_all::<BatchedSend, _>([]).await.unwrap(), 0);
+
+    println!("batch_send: Send to the subscription");
+    let mut subscription = subscribe::<BatchedSend>().await;
+    assert_eq!(notify_all::<BatchedSend, _>(1..=4).await.unwrap(), 4);
+    let mut buffer = Vec::new();
+    assert_eq!(subscription.recv_many(&mut buffer, 8).await, 4);
+    assert_eq!(buffer, [1, 2, 3, 4]);
+    subscription.close().await;
+}
@@ -0,0 +1,296 @@
+use super::{intercept, Broadcast, BroadcastChannel, CHANNELS};
+use crate::{
+    common::UntypedBox,
+    layer::Stage,
+    rate::{Debounce, Sample, Throttle},
+};
+use std::{any::TypeId, collections::HashMap, marker::PhantomData, mem, time::Duration};
+use tokio::sync::broadcast::{
+    self,
+    error::{RecvError, TryRecvError},
+    Receiver, Sender,
+};
+
+/// Topic level separator
+const SEPARATOR: char = '.';
+/// Wildcard for exactly one topic level
+const SINGLE_LEVEL: &str = "*";
+/// Wildcard for any number of the last topic levels
+const MULTI_LEVEL: &str = "#";
+
+/// Broadcast notification subscription to the topics
+/// that match the filter
+///
+/// See [subscribe_topic]
+pub struct TopicSubscription<B: Broadcast> {
+    filter: String,
+    receiver: Option<Receiver<(String, B::Payload)>>,
+}
+
+/// Subscribe to broadcast notifications
+/// with topics that match the `filter`
+///
+/// Topics consist of the levels separated by `.`, e.g. `eq.NASDAQ.AAPL`,
+/// in the filter `*` matches exactly one level and `#` as the last level
+/// matches any number of the remaining levels,
+/// e.g. `eq.NASDAQ.*` or `eq.#` match `eq.NASDAQ.AAPL`
+///
+/// Topic subscriptions receive only notifications sent by [notify_topic]
+///
+/// Returns [InvalidTopic] if `#` is not the last level of the `filter`
+pub async fn subscribe_topic<B: Broadcast>(
+    filter: &str,
+) -> Result<TopicSubscription<B>, InvalidTopic<B>> {
+    let levels: Vec<&str> = filter.split(SEPARATOR).collect();
+    if levels[..levels.len() - 1].contains(&MULTI_LEVEL) {
+        return Err(InvalidTopic::new(filter));
+    }
+    let mut channels = CHANNELS.write().await;
+    let channel = BroadcastChannel::<B>::get_or_insert(&mut channels);
+    let rx = channel.topics.subscribe(&levels, B::BUFFER_SIZE);
+    Ok(TopicSubscription {
+        filter: filter.to_string(),
+        receiver: Some(rx),
+    })
+}
+
+/// Sends a payload with the topic to the [TopicSubscription]s
+/// with the matching filters
+///
+/// The payload rejected by a [Layer](crate::layer::Layer)
+/// or with the `*` or `#` wildcard as a level of the `topic` is dropped
+pub async fn notify_topic<B: Broadcast>(topic: &str, mut payload: B::Payload) {
+    let levels: Vec<&str> = topic.split(SEPARATOR).collect();
+    if levels.contains(&SINGLE_LEVEL) || levels.contains(&MULTI_LEVEL) {
+        return;
+    }
+    if !intercept::<B>(Stage::Send, Some(topic), &mut payload) {
+        return;
+    }
+    let id = id!(B);
+    let channels = CHANNELS.read().await;
+    let channel = match channels.get(&id) {
+        Some(channel) => channel,
+        None => return,
+    };
+    let channel: &BroadcastChannel<B> = unsafe { channel.get_ref() };
+    channel.topics.publish(&levels, topic, &payload);
+}
+
+/// Error of the [subscribe_topic] fn,
+/// the topic filter has `#` not in the last level
+pub struct InvalidTopic<B: Broadcast> {
+    /// The invalid topic filter
+    pub filter: String,
+    _broadcast: PhantomData<fn() -> B>,
+}
+
+impl<B: Broadcast> InvalidTopic<B> {
+    fn new(filter: &str) -> Self {
+        Self {
+            filter: filter.to_string(),
+            _broadcast: PhantomData,
+        }
+    }
+}
+
+impl<B: Broadcast> std::fmt::Debug for InvalidTopic<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InvalidTopic in {}: {}", B::DEBUG_NAME, self.filter)
+    }
+}
+
+impl<B: Broadcast> TopicSubscription<B> {
+    /// Receives the next topic and value for this Subscription
+    ///
+    /// Notifications rejected by a [Layer](crate::layer::Layer) are skipped
+    pub async fn recv(&mut self) -> (String, B::Payload) {
+        let receiver = match &mut self.receiver {
+            Some(receiver) => receiver,
+            None => unreachable!(),
+        };
+        loop {
+            match receiver.recv().await {
+                Ok((topic, mut payload)) => {
+                    if intercept::<B>(Stage::Receive, Some(&topic), &mut payload) {
+                        return (topic, payload);
+                    }
+                }
+                Err(RecvError::Closed) => unreachable!(),
+                Err(RecvError::Lagged(_)) => {}
+            }
+        }
+    }
+
+    /// Returns the topic filter of this Subscription
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    /// Receives up to `max` topics and values for this Subscription into the `buffer`
+    ///
+    /// Waits for at least one value, then takes the buffered ones
+    /// without waiting
+    ///
+    /// Returns the number of the received values, 0 if `max` is 0
+    pub async fn recv_many(&mut self, buffer: &mut Vec<(String, B::Payload)>, max: usize) -> usize {
+        if max == 0 {
+            return 0;
+        }
+        buffer.push(self.recv().await);
+        let receiver = match &mut self.receiver {
+            Some(receiver) => receiver,
+            None => unreachable!(),
+        };
+        let mut count = 1;
+        while count < max {
+            match receiver.try_recv() {
+                Ok((topic, mut payload)) => {
+                    if intercept::<B>(Stage::Receive, Some(&topic), &mut payload) {
+                        buffer.push((topic, payload));
+                        count += 1;
+                    }
+                }
+                Err(TryRecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+        count
+    }
+
+    /// Adapts the subscription to receive a payload
+    /// only after no newer payloads are sent for the quiet `period`
+    pub fn debounce(self, period: Duration) -> Debounce<Self> {
+        Debounce::new(self, period)
+    }
+
+    /// Adapts the subscription to receive at most one payload per `period`
+    pub fn throttle(self, period: Duration) -> Throttle<Self> {
+        Throttle::new(self, period)
+    }
+
+    /// Adapts the subscription to receive the latest payload once per `period`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero
+    pub fn sample(self, period: Duration) -> Sample<Self> {
+        Sample::new(self, period)
+    }
+
+    /// Closes the subscription
+    ///
+    /// Closing the subscription with this method
+    /// is preferable for performance reasons
+    pub async fn close(mut self) {
+        drop(self.receiver.take());
+        let mut channels = CHANNELS.write().await;
+        remove_unused_topic::<B>(&mut channels, &self.filter);
+    }
+}
+
+impl<B: Broadcast> Drop for TopicSubscription<B> {
+    fn drop(&mut self) {
+        if self.receiver.is_some() {
+            let filter = mem::take(&mut self.filter);
+            CHANNELS.update_when_possible(move |channels| {
+                remove_unused_topic::<B>(channels, &filter);
+            });
+            #[cfg(debug_assertions)]
+            eprintln!(
+                "Subscription for {} closed in slow manner! Use close method for optimize it",
+                B::DEBUG_NAME
+            );
+        }
+    }
+}
+
+fn remove_unused_topic<B: Broadcast>(channels: &mut HashMap<TypeId, UntypedBox>, filter: &str) {
+    if let Some(channel) = channels.get_mut(&id!(B)) {
+        let channel: &mut BroadcastChannel<B> = unsafe { channel.get_mut() };
+        let levels: Vec<&str> = filter.split(SEPARATOR).collect();
+        channel.topics.remove_unused(&levels);
+    }
+    BroadcastChannel::<B>::remove_unused(channels);
+}
+
+/// Node of the topic filters tree
+pub(super) struct TopicNode<P> {
+    sender: Option<Sender<(String, P)>>,
+    children: HashMap<String, TopicNode<P>>,
+}
+
+impl<P: Clone> TopicNode<P> {
+    pub(super) fn new() -> Self {
+        Self {
+            sender: None,
+            children: HashMap::new(),
+        }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.sender.is_none() && self.children.is_empty()
+    }
+
+    fn subscribe(&mut self, filter: &[&str], buffer_size: usize) -> Receiver<(String, P)> {
+        match filter.split_first() {
+            Some((level, rest)) => self
+                .children
+                .entry(level.to_string())
+                .or_insert_with(TopicNode::new)
+                .subscribe(rest, buffer_size),
+            None => match &self.sender {
+                Some(sender) => sender.subscribe(),
+                None => {
+                    let (tx, rx) = broadcast::channel(buffer_size);
+                    self.sender = Some(tx);
+                    rx
+                }
+            },
+        }
+    }
+
+    fn publish(&self, topic_levels: &[&str], topic: &str, payload: &P) {
+        if let Some(node) = self.children.get(MULTI_LEVEL) {
+            node.send(topic, payload);
+        }
+        match topic_levels.split_first() {
+            Some((level, rest)) => {
+                if let Some(node) = self.children.get(*level) {
+                    node.publish(rest, topic, payload);
+                }
+                if let Some(node) = self.children.get(SINGLE_LEVEL) {
+                    node.publish(rest, topic, payload);
+                }
+            }
+            None => self.send(topic, payload),
+        }
+    }
+
+    fn send(&self, topic: &str, payload: &P) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send((topic.to_string(), payload.clone()));
+        }
+    }
+
+    /// Removes the filter if it has no subscribers
+    fn remove_unused(&mut self, filter: &[&str]) {
+        match filter.split_first() {
+            Some((level, rest)) => {
+                if let Some(node) = self.children.get_mut(*level) {
+                    node.remove_unused(rest);
+                    if node.is_empty() {
+                        self.children.remove(*level);
+                    }
+                }
+            }
+            None => {
+                if let Some(sender) = &self.sender {
+                    if sender.receiver_count() == 0 {
+                        self.sender = None;
+                    }
+                }
+            }
+        }
+    }
+}
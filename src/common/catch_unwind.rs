@@ -0,0 +1,77 @@
+use std::{
+    any::Any,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Calls `f` catching the panic message if it panics
+pub(crate) fn catch_unwind<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(panic_message)
+}
+
+/// Future that catches the panic message if the inner future panics
+pub(crate) struct CatchUnwind<F> {
+    future: F,
+}
+
+impl<F: Future> CatchUnwind<F> {
+    pub(crate) fn new(future: F) -> Self {
+        Self { future }
+    }
+}
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, String>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // it is correct because future is structurally pinned and never moved
+        let future = unsafe { self.map_unchecked_mut(|this| &mut this.future) };
+        match catch_unwind(|| future.poll(cx)) {
+            Ok(poll) => poll.map(Ok),
+            Err(message) => Poll::Ready(Err(message)),
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "Box<dyn Any>".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn catch_sync_panic() {
+        assert_eq!(catch_unwind(|| 1), Ok(1));
+        assert_eq!(
+            catch_unwind(|| -> i32 { panic!("sync") }),
+            Err("sync".to_string())
+        );
+        assert_eq!(
+            catch_unwind(|| -> i32 { panic!("formatted {}", 1) }),
+            Err("formatted 1".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn catch_async_panic() {
+        assert_eq!(CatchUnwind::new(async { 1 }).await, Ok(1));
+        assert_eq!(
+            CatchUnwind::new(async {
+                tokio::task::yield_now().await;
+                panic!("async")
+            })
+            .await,
+            Err::<(), _>("async".to_string())
+        );
+    }
+}
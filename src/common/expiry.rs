@@ -0,0 +1,39 @@
+use super::OnceCell;
+use parking_lot::Mutex;
+use std::{any::TypeId, collections::HashMap, time::Duration};
+use tokio::time::Instant;
+
+/// Returns the instant when the item sent now with the `ttl` expires
+pub(crate) fn expires_at(ttl: Option<Duration>) -> Option<Instant> {
+    ttl.map(|ttl| Instant::now() + ttl)
+}
+
+/// Returns true if the item expiring at `expires` is expired
+pub(crate) fn is_expired(expires: Option<Instant>) -> bool {
+    expires.is_some_and(|expires| expires <= Instant::now())
+}
+
+/// Counters of the expired items by their type ids
+pub(crate) struct ExpiryCounters {
+    counters: OnceCell<Mutex<HashMap<TypeId, u64>>>,
+}
+
+impl ExpiryCounters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            counters: OnceCell::new(),
+        }
+    }
+
+    pub(crate) fn increment(&self, id: TypeId) {
+        *self.counters().lock().entry(id).or_default() += 1;
+    }
+
+    pub(crate) fn get(&self, id: TypeId) -> u64 {
+        self.counters().lock().get(&id).copied().unwrap_or_default()
+    }
+
+    fn counters(&self) -> &Mutex<HashMap<TypeId, u64>> {
+        self.counters.get_or_init(Default::default)
+    }
+}
@@ -4,10 +4,18 @@ macro_rules! id {
     };
 }
 
+mod catch_unwind;
+mod expiry;
 mod once_cell;
+mod queue;
+mod random;
 mod static_type_map;
 mod untyped_box;
 
+pub(crate) use catch_unwind::{catch_unwind, CatchUnwind};
+pub(crate) use expiry::{expires_at, is_expired, ExpiryCounters};
 pub(crate) use once_cell::OnceCell;
+pub(crate) use queue::{Queue, TryPushError};
+pub(crate) use random::random;
 pub(crate) use static_type_map::StaticTypeMap;
 pub(crate) use untyped_box::UntypedBox;
@@ -0,0 +1,557 @@
+use crate::priority::Priority;
+use parking_lot::Mutex;
+use std::{
+    cmp::Reverse,
+    collections::{HashSet, VecDeque},
+};
+use tokio::sync::{Notify, Semaphore, TryAcquireError};
+
+const LANES: usize = 3;
+
+/// Multi-lane queue with a single receiver
+///
+/// Higher priority lanes are drained first,
+/// unless the lower ones wait longer than the starvation limit
+///
+/// The queue with the dedup key keeps only the latest item per key,
+/// the newer item replaces the queued one in its place
+pub(crate) struct Queue<T> {
+    state: Mutex<State<T>>,
+    /// Free slots of the bounded queue
+    slots: Option<Semaphore>,
+    received: Notify,
+    starvation_limit: Option<u32>,
+}
+
+/// Error of [Queue::try_push]
+pub(crate) enum TryPushError<T> {
+    Full(T),
+    Closed(T),
+}
+
+struct State<T> {
+    lanes: [VecDeque<T>; LANES],
+    closed: bool,
+    /// Items received while each lane is waiting
+    waited: [u32; LANES],
+    key: Option<fn(&T) -> u64>,
+    /// Keys of the queued items
+    keys: HashSet<u64>,
+}
+
+impl<T> Queue<T> {
+    /// Creates the queue, `capacity` of 0 means unbounded
+    /// and `starvation_limit` of 0 is the same as None
+    pub(crate) fn new(capacity: usize, starvation_limit: Option<u32>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                lanes: Default::default(),
+                closed: false,
+                waited: [0; LANES],
+                key: None,
+                keys: HashSet::new(),
+            }),
+            slots: match capacity {
+                0 => None,
+                capacity => Some(Semaphore::new(capacity)),
+            },
+            received: Notify::new(),
+            starvation_limit: starvation_limit.filter(|&limit| limit > 0),
+        }
+    }
+
+    /// Sets the dedup key of the items
+    pub(crate) fn with_dedup(self, key: fn(&T) -> u64) -> Self {
+        self.state.lock().key = Some(key);
+        self
+    }
+
+    /// Pushes the item to the lane of the priority,
+    /// waits for a free slot if the queue is full
+    ///
+    /// Returns the item back if the queue is closed
+    pub(crate) async fn push(&self, item: T, priority: Priority) -> Result<(), T> {
+        let item = match self.replace(item, priority) {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+        if let Some(slots) = &self.slots {
+            match slots.acquire().await {
+                Ok(permit) => permit.forget(),
+                Err(_) => return Err(item),
+            }
+        }
+        let mut state = self.state.lock();
+        if state.closed {
+            return Err(item);
+        }
+        if !state.insert(item, priority) {
+            self.release(1);
+        }
+        drop(state);
+        self.received.notify_one();
+        Ok(())
+    }
+
+    /// Pushes the items to the lane of the priority in bulk,
+    /// waits for the free slots if the queue is full
+    ///
+    /// Returns the items that are not pushed if the queue is closed
+    pub(crate) async fn push_all(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        priority: Priority,
+    ) -> Result<(), VecDeque<T>> {
+        let mut items: VecDeque<T> = items.into_iter().collect();
+        while !items.is_empty() {
+            let mut taken = match &self.slots {
+                Some(slots) => {
+                    // wait for one slot, then take the other free ones
+                    match slots.acquire().await {
+                        Ok(permit) => permit.forget(),
+                        Err(_) => return Err(items),
+                    }
+                    let mut taken = 1;
+                    while taken < items.len() {
+                        match slots.try_acquire() {
+                            Ok(permit) => permit.forget(),
+                            Err(_) => break,
+                        }
+                        taken += 1;
+                    }
+                    taken
+                }
+                None => items.len(),
+            };
+            let mut state = self.state.lock();
+            if state.closed {
+                return Err(items);
+            }
+            while taken > 0 {
+                let item = match items.pop_front() {
+                    Some(item) => item,
+                    None => break,
+                };
+                if state.insert(item, priority) {
+                    taken -= 1;
+                }
+            }
+            drop(state);
+            self.release(taken);
+            self.received.notify_one();
+        }
+        Ok(())
+    }
+
+    /// Pushes the item to the lane of the priority if the queue is not full
+    pub(crate) fn try_push(&self, item: T, priority: Priority) -> Result<(), TryPushError<T>> {
+        let item = match self.replace(item, priority) {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+        if let Some(slots) = &self.slots {
+            match slots.try_acquire() {
+                Ok(permit) => permit.forget(),
+                Err(TryAcquireError::NoPermits) => return Err(TryPushError::Full(item)),
+                Err(TryAcquireError::Closed) => return Err(TryPushError::Closed(item)),
+            }
+        }
+        let mut state = self.state.lock();
+        if state.closed {
+            return Err(TryPushError::Closed(item));
+        }
+        if !state.insert(item, priority) {
+            self.release(1);
+        }
+        drop(state);
+        self.received.notify_one();
+        Ok(())
+    }
+
+    /// Pushes the item to the lane of the priority,
+    /// if the queue is full drops the oldest item of the lowest priority
+    ///
+    /// Returns the item back if the queue is closed
+    pub(crate) async fn push_dropping_oldest(&self, item: T, priority: Priority) -> Result<(), T> {
+        self.push_overflowing(item, priority, None).await
+    }
+
+    /// Pushes the item to the lane of the priority,
+    /// if the queue is full merges it into the last item of the same lane
+    /// or of the lowest priority lane
+    ///
+    /// Returns the item back if the queue is closed
+    pub(crate) async fn push_merging(
+        &self,
+        item: T,
+        priority: Priority,
+        merge: fn(&mut T, T),
+    ) -> Result<(), T> {
+        self.push_overflowing(item, priority, Some(merge)).await
+    }
+
+    async fn push_overflowing(
+        &self,
+        item: T,
+        priority: Priority,
+        merge: Option<fn(&mut T, T)>,
+    ) -> Result<(), T> {
+        let mut item = item;
+        loop {
+            item = match self.try_push(item, priority) {
+                Ok(()) => return Ok(()),
+                Err(TryPushError::Closed(item)) => return Err(item),
+                Err(TryPushError::Full(item)) => item,
+            };
+            item = match self.overflow(item, priority, merge) {
+                Ok(()) => return Ok(()),
+                Err(TryPushError::Closed(item)) => return Err(item),
+                Err(TryPushError::Full(item)) => item,
+            };
+            // the slots are taken by the pushes in progress
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Takes the place of the oldest lowest priority item
+    /// or merges into the last item
+    fn overflow(
+        &self,
+        item: T,
+        priority: Priority,
+        merge: Option<fn(&mut T, T)>,
+    ) -> Result<(), TryPushError<T>> {
+        let mut state = self.state.lock();
+        if state.closed {
+            return Err(TryPushError::Closed(item));
+        }
+        let lane = match merge {
+            Some(_) if !state.lanes[priority.lane()].is_empty() => Some(priority.lane()),
+            _ => state.lanes.iter().rposition(|lane| !lane.is_empty()),
+        };
+        let lane = match lane {
+            Some(lane) => lane,
+            None => return Err(TryPushError::Full(item)),
+        };
+        match merge {
+            Some(merge) => {
+                let replaced = state.merge_last(lane, item, merge);
+                drop(state);
+                self.release(replaced);
+            }
+            None => {
+                // the slot of the dropped item is taken by the new one
+                state.pop_front(lane);
+                if !state.insert(item, priority) {
+                    self.release(1);
+                }
+                drop(state);
+                self.received.notify_one();
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the queued item with the same key,
+    /// returns the item back if there is no such item
+    fn replace(&self, item: T, priority: Priority) -> Option<T> {
+        let mut state = self.state.lock();
+        if state.closed || !state.is_queued(&item) {
+            return Some(item);
+        }
+        state.insert(item, priority);
+        drop(state);
+        self.received.notify_one();
+        None
+    }
+
+    /// Frees the slots taken by the replacing or popped items
+    fn release(&self, count: usize) {
+        if let Some(slots) = &self.slots {
+            slots.add_permits(count);
+        }
+    }
+
+    /// Pops the next item, waits for it if the queue is empty
+    ///
+    /// Returns None if the queue is closed and empty
+    pub(crate) async fn pop(&self) -> Option<T> {
+        loop {
+            if let Some(item) = self.try_pop() {
+                return Some(item);
+            }
+            if self.is_closed() {
+                return None;
+            }
+            self.received.notified().await;
+        }
+    }
+
+    /// Pops the next item if there is any
+    pub(crate) fn try_pop(&self) -> Option<T> {
+        let mut state = self.state.lock();
+        let item = state.pop(self.starvation_limit)?;
+        drop(state);
+        self.release(1);
+        Some(item)
+    }
+
+    /// Closes the queue, the queued items can still be popped
+    pub(crate) fn close(&self) {
+        self.state.lock().closed = true;
+        if let Some(slots) = &self.slots {
+            slots.close();
+        }
+        self.received.notify_one();
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.state.lock().closed
+    }
+}
+
+impl<T> State<T> {
+    fn pop(&mut self, starvation_limit: Option<u32>) -> Option<T> {
+        let top = self.lanes.iter().position(|lane| !lane.is_empty())?;
+        // the lower lane waiting the longest over the limit is received first
+        let starved = starvation_limit.and_then(|limit| {
+            (top + 1..LANES)
+                .filter(|&lane| !self.lanes[lane].is_empty() && self.waited[lane] >= limit)
+                .max_by_key(|&lane| (self.waited[lane], Reverse(lane)))
+        });
+        let lane = starved.unwrap_or(top);
+        for (other, waited) in self.waited.iter_mut().enumerate() {
+            *waited = match other == lane || self.lanes[other].is_empty() {
+                true => 0,
+                false => waited.saturating_add(1),
+            };
+        }
+        self.pop_front(lane)
+    }
+
+    fn pop_front(&mut self, lane: usize) -> Option<T> {
+        let item = self.lanes[lane].pop_front()?;
+        if let Some(key) = self.key {
+            self.keys.remove(&key(&item));
+        }
+        Some(item)
+    }
+
+    fn is_queued(&self, item: &T) -> bool {
+        self.key.is_some_and(|key| self.keys.contains(&key(item)))
+    }
+
+    /// Queues the item, returns false if it replaced the queued item with the same key
+    fn insert(&mut self, item: T, priority: Priority) -> bool {
+        let key = match self.key {
+            Some(key) => key,
+            None => {
+                self.lanes[priority.lane()].push_back(item);
+                return true;
+            }
+        };
+        let item_key = key(&item);
+        if self.keys.insert(item_key) {
+            self.lanes[priority.lane()].push_back(item);
+            return true;
+        }
+        let queued = self.lanes.iter().enumerate().find_map(|(lane, items)| {
+            let index = items.iter().position(|queued| key(queued) == item_key)?;
+            Some((lane, index))
+        });
+        match queued {
+            Some((lane, index)) if lane == priority.lane() => self.lanes[lane][index] = item,
+            Some((lane, index)) => {
+                self.lanes[lane].remove(index);
+                self.lanes[priority.lane()].push_back(item);
+            }
+            None => {
+                self.lanes[priority.lane()].push_back(item);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Merges the item into the last item of the lane,
+    /// returns the number of the queued items replaced by the merged one
+    fn merge_last(&mut self, lane: usize, item: T, merge: fn(&mut T, T)) -> usize {
+        let last = match self.lanes[lane].back_mut() {
+            Some(last) => last,
+            None => return 0,
+        };
+        let key = match self.key {
+            Some(key) => key,
+            None => {
+                merge(last, item);
+                return 0;
+            }
+        };
+        self.keys.remove(&key(last));
+        merge(last, item);
+        let merged_key = key(last);
+        if self.keys.insert(merged_key) {
+            return 0;
+        }
+        // the merged item takes the key of another queued item
+        let last = self.lanes[lane].len() - 1;
+        let queued = self.lanes.iter().enumerate().find_map(|(other, items)| {
+            let index = items.iter().enumerate().position(|(index, queued)| {
+                (other, index) != (lane, last) && key(queued) == merged_key
+            })?;
+            Some((other, index))
+        });
+        match queued {
+            Some((other, index)) => {
+                self.lanes[other].remove(index);
+                1
+            }
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn priority_lanes() {
+        let queue = Queue::new(0, None);
+        for (item, priority) in [
+            (1, Priority::Low),
+            (2, Priority::Normal),
+            (3, Priority::High),
+            (4, Priority::Normal),
+            (5, Priority::High),
+        ] {
+            queue.push(item, priority).await.unwrap();
+        }
+        let mut items = Vec::new();
+        while let Some(item) = queue.try_pop() {
+            items.push(item);
+        }
+        assert_eq!(items, [3, 5, 2, 4, 1]);
+    }
+
+    #[tokio::test]
+    async fn starvation_limit() {
+        let queue = Queue::new(0, Some(2));
+        for item in 0..5 {
+            queue.push(item, Priority::High).await.unwrap();
+        }
+        queue.push(10, Priority::Low).await.unwrap();
+        let mut items = Vec::new();
+        while let Some(item) = queue.try_pop() {
+            items.push(item);
+        }
+        assert_eq!(items, [0, 1, 10, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn starvation_limit_lanes() {
+        let queue = Queue::new(0, Some(1));
+        queue.push("low", Priority::Low).await.unwrap();
+        let mut items = Vec::new();
+        for _ in 0..3 {
+            // the higher lanes are never empty
+            queue.push("high", Priority::High).await.unwrap();
+            queue.push("normal", Priority::Normal).await.unwrap();
+            items.push(queue.try_pop().unwrap());
+            items.push(queue.try_pop().unwrap());
+        }
+        assert_eq!(items, ["high", "low", "normal", "high", "normal", "high"]);
+
+        // the limit of 0 does not starve the higher lanes
+        let queue = Queue::new(0, Some(0));
+        queue.push(1, Priority::Low).await.unwrap();
+        queue.push(2, Priority::High).await.unwrap();
+        queue.push(3, Priority::High).await.unwrap();
+        let mut items = Vec::new();
+        while let Some(item) = queue.try_pop() {
+            items.push(item);
+        }
+        assert_eq!(items, [2, 3, 1]);
+    }
+
+    #[tokio::test]
+    async fn bounded_and_closed() {
+        let queue = Arc::new(Queue::new(1, None));
+        queue.push(1, Priority::Normal).await.unwrap();
+        let push = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push(2, Priority::High).await }
+        });
+        assert_eq!(queue.pop().await, Some(1));
+        push.await.unwrap().unwrap();
+        assert_eq!(queue.pop().await, Some(2));
+
+        queue.push(3, Priority::Normal).await.unwrap();
+        queue.close();
+        assert_eq!(queue.push(4, Priority::Normal).await, Err(4));
+        assert_eq!(queue.pop().await, Some(3));
+        assert_eq!(queue.pop().await, None);
+    }
+
+    #[tokio::test]
+    async fn dedup_by_key() {
+        let queue = Queue::new(2, None).with_dedup(|item: &(u8, i32)| item.0 as u64);
+        queue.push((1, 10), Priority::Normal).await.unwrap();
+        queue.push((2, 20), Priority::Normal).await.unwrap();
+        // the queue is full, but the queued items are replaced
+        queue.push((1, 11), Priority::Normal).await.unwrap();
+        assert!(queue.try_push((2, 21), Priority::High).is_ok());
+        assert!(matches!(
+            queue.try_push((3, 30), Priority::Normal),
+            Err(TryPushError::Full(_))
+        ));
+        assert_eq!(queue.try_pop(), Some((2, 21)));
+        queue.push((3, 30), Priority::Normal).await.unwrap();
+        assert_eq!(queue.try_pop(), Some((1, 11)));
+        queue.push((1, 12), Priority::Normal).await.unwrap();
+        assert_eq!(queue.try_pop(), Some((3, 30)));
+        assert_eq!(queue.try_pop(), Some((1, 12)));
+        assert_eq!(queue.try_pop(), None);
+    }
+
+    #[tokio::test]
+    async fn dedup_merging() {
+        let queue = Queue::new(2, None).with_dedup(|item: &u32| *item as u64);
+        queue.push(3, Priority::Normal).await.unwrap();
+        queue.push(1, Priority::Normal).await.unwrap();
+        // 1 + 2 takes the key of the queued 3, which is replaced
+        queue
+            .push_merging(2, Priority::Normal, |last, item| *last += item)
+            .await
+            .unwrap();
+        assert!(queue.try_push(5, Priority::Normal).is_ok());
+        assert_eq!(queue.try_pop(), Some(3));
+        assert_eq!(queue.try_pop(), Some(5));
+        assert_eq!(queue.try_pop(), None);
+
+        queue.push(3, Priority::Normal).await.unwrap();
+        queue.push(4, Priority::Normal).await.unwrap();
+        assert_eq!(queue.try_pop(), Some(3));
+        assert_eq!(queue.try_pop(), Some(4));
+    }
+
+    #[tokio::test]
+    async fn push_all() {
+        let queue = Arc::new(Queue::new(2, None));
+        let push = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push_all(1..=5, Priority::Normal).await }
+        });
+        let mut items = Vec::new();
+        while items.len() < 5 {
+            items.push(queue.pop().await.unwrap());
+        }
+        assert!(push.await.unwrap().is_ok());
+        assert_eq!(items, [1, 2, 3, 4, 5]);
+
+        queue.close();
+        let rest = queue.push_all([6, 7], Priority::Normal).await.unwrap_err();
+        assert_eq!(rest, [6, 7]);
+    }
+}
@@ -0,0 +1,18 @@
+use super::OnceCell;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+static STATE: OnceCell<RandomState> = OnceCell::new();
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a pseudo random number
+///
+/// It is not suitable for cryptographic purposes
+pub(crate) fn random() -> u64 {
+    let mut hasher = STATE.get_or_init(RandomState::new).build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
@@ -4,20 +4,29 @@ use std::{
     any::TypeId,
     collections::HashMap,
     mem,
+    ops::{Deref, DerefMut},
     sync::atomic::{AtomicBool, Ordering},
 };
-use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::{Notify, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 type Map<V> = HashMap<TypeId, V>;
+type Update<V> = Box<dyn FnOnce(&mut Map<V>) + Send>;
 
 pub(crate) struct StaticTypeMap<V = UntypedBox> {
     inner: OnceCell<StaticTypeMapInner<V>>,
 }
 
 struct StaticTypeMapInner<V> {
-    has_to_remove: AtomicBool,
-    to_remove: Mutex<Vec<TypeId>>,
+    has_to_update: AtomicBool,
+    to_update: Mutex<Vec<Update<V>>>,
     map: RwLock<Map<V>>,
+    changed: Notify,
+}
+
+/// Write guard that wakes up the waiters of the map when dropped
+pub(crate) struct WriteGuard<'a, V> {
+    map: RwLockWriteGuard<'a, Map<V>>,
+    changed: &'a Notify,
 }
 
 impl<V> StaticTypeMap<V> {
@@ -31,31 +40,75 @@ impl<V> StaticTypeMap<V> {
         self.get_or_init().map.read().await
     }
 
-    pub(crate) async fn write(&self) -> RwLockWriteGuard<'_, Map<V>> {
+    pub(crate) async fn write(&self) -> WriteGuard<'_, V> {
         let inner = self.get_or_init();
         let mut map = inner.map.write().await;
-        if inner.has_to_remove.load(Ordering::Relaxed) {
-            let mut to_remove = inner.to_remove.lock();
-            inner.has_to_remove.store(false, Ordering::Release);
-            let to_remove = mem::replace(&mut *to_remove, Vec::new());
-            for id in to_remove.into_iter() {
-                map.remove(&id);
+        if inner.has_to_update.load(Ordering::Relaxed) {
+            let mut to_update = inner.to_update.lock();
+            inner.has_to_update.store(false, Ordering::Release);
+            let to_update = mem::take(&mut *to_update);
+            for update in to_update.into_iter() {
+                update(&mut map);
+            }
+        }
+        WriteGuard {
+            map,
+            changed: &inner.changed,
+        }
+    }
+
+    /// Waits until the value with the id matches the condition
+    ///
+    /// The condition is checked again after every write to the map
+    pub(crate) async fn wait_until(&self, id: TypeId, condition: impl Fn(&V) -> bool) {
+        let inner = self.get_or_init();
+        loop {
+            let changed = inner.changed.notified();
+            if self.read().await.get(&id).is_some_and(&condition) {
+                return;
             }
+            changed.await;
         }
-        map
     }
 
     pub(crate) fn remove_when_possible(&self, id: TypeId) {
+        self.update_when_possible(move |map| {
+            map.remove(&id);
+        });
+    }
+
+    pub(crate) fn update_when_possible(&self, update: impl FnOnce(&mut Map<V>) + Send + 'static) {
         let inner = self.get_or_init();
-        inner.to_remove.lock().push(id);
-        inner.has_to_remove.store(true, Ordering::Release);
+        inner.to_update.lock().push(Box::new(update));
+        inner.has_to_update.store(true, Ordering::Release);
     }
 
     fn get_or_init(&self) -> &StaticTypeMapInner<V> {
         self.inner.get_or_init(|| StaticTypeMapInner {
-            has_to_remove: AtomicBool::new(false),
-            to_remove: Mutex::new(Vec::new()),
+            has_to_update: AtomicBool::new(false),
+            to_update: Mutex::new(Vec::new()),
             map: RwLock::new(Map::new()),
+            changed: Notify::new(),
         })
     }
 }
+
+impl<V> Deref for WriteGuard<'_, V> {
+    type Target = Map<V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<V> DerefMut for WriteGuard<'_, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.map
+    }
+}
+
+impl<V> Drop for WriteGuard<'_, V> {
+    fn drop(&mut self) {
+        self.changed.notify_waiters();
+    }
+}
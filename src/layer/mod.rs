@@ -0,0 +1,231 @@
+//! Layers
+//!
+//! Interceptors of the sent and received messages
+//! for logging, metrics, validation etc.
+
+use crate::common::OnceCell;
+use parking_lot::RwLock;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+#[cfg(test)]
+mod test;
+
+static LAYERS: OnceCell<RwLock<Layers>> = OnceCell::new();
+static LAYERS_COUNT: AtomicUsize = AtomicUsize::new(0);
+static LAYER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// An interceptor of the messages
+///
+/// Layers are called in the order of registration,
+/// global layers are called before the per type ones
+///
+/// Any layer can reject the message with a reason:
+/// - a rejected [request](crate::request::request) or
+///   [notify](crate::notification::notify) fails with the `Rejected` error
+/// - a rejected [broadcast](crate::broadcast::notify) is dropped
+/// - a rejected received request is responded with
+///   [RequestError::Rejected](crate::request::RequestError::Rejected)
+/// - a rejected received notification is skipped
+pub trait Layer: Send + Sync + 'static {
+    /// Called before the message is sent
+    fn on_send(&self, message: &mut Message<'_>) -> Result<(), String> {
+        let _ = message;
+        Ok(())
+    }
+
+    /// Called when the message is received
+    /// by the listener or the subscriber
+    fn on_receive(&self, message: &mut Message<'_>) -> Result<(), String> {
+        let _ = message;
+        Ok(())
+    }
+}
+
+/// Kind of the intercepted message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// [Request](crate::request::Request)
+    Request,
+    /// [Notification](crate::notification::Notification)
+    Notification,
+    /// [Broadcast](crate::broadcast::Broadcast)
+    Broadcast,
+}
+
+/// Stage of the intercepted message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// The message is going to be sent
+    Send,
+    /// The message is received
+    Receive,
+}
+
+/// The intercepted message
+pub struct Message<'a> {
+    kind: Kind,
+    stage: Stage,
+    type_id: TypeId,
+    name: &'static str,
+    topic: Option<&'a str>,
+    payload: &'a mut dyn Any,
+}
+
+impl Message<'_> {
+    /// Returns the kind of the message
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Returns the stage of the message
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// Returns the debug name of the message type
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Checks if the message is of the type `T`
+    pub fn is<T: 'static>(&self) -> bool {
+        self.type_id == id!(T)
+    }
+
+    /// Returns the topic of the [topic broadcast](crate::broadcast::notify_topic)
+    pub fn topic(&self) -> Option<&str> {
+        self.topic
+    }
+
+    /// Returns the payload if it is of the type `P`
+    pub fn payload<P: 'static>(&self) -> Option<&P> {
+        self.payload.downcast_ref()
+    }
+
+    /// Returns the mutable payload if it is of the type `P`
+    pub fn payload_mut<P: 'static>(&mut self) -> Option<&mut P> {
+        self.payload.downcast_mut()
+    }
+}
+
+/// Handle of the registered layer
+#[derive(Debug)]
+pub struct LayerHandle {
+    id: usize,
+    type_id: Option<TypeId>,
+}
+
+impl LayerHandle {
+    /// Removes the layer
+    ///
+    /// Dropping the handle keeps the layer registered
+    pub fn remove(self) {
+        let mut layers = layers().write();
+        let entries = match self.type_id {
+            Some(type_id) => match layers.typed.get_mut(&type_id) {
+                Some(entries) => entries,
+                None => return,
+            },
+            None => &mut layers.global,
+        };
+        let len = entries.len();
+        entries.retain(|(id, _)| *id != self.id);
+        if entries.len() < len {
+            LAYERS_COUNT.fetch_sub(1, Ordering::Release);
+        }
+        if let Some(type_id) = self.type_id {
+            if layers.typed.get(&type_id).is_some_and(Vec::is_empty) {
+                layers.typed.remove(&type_id);
+            }
+        }
+    }
+}
+
+/// Registers the layer for the messages of all types
+pub fn add_layer(layer: impl Layer) -> LayerHandle {
+    let id = LAYER_ID.fetch_add(1, Ordering::Relaxed);
+    layers().write().global.push((id, Arc::new(layer)));
+    LAYERS_COUNT.fetch_add(1, Ordering::Release);
+    LayerHandle { id, type_id: None }
+}
+
+/// Registers the layer for the messages of the type `T`
+///
+/// `T` is the [Request](crate::request::Request),
+/// [Notification](crate::notification::Notification)
+/// or [Broadcast](crate::broadcast::Broadcast) type
+pub fn add_layer_for<T: 'static>(layer: impl Layer) -> LayerHandle {
+    let id = LAYER_ID.fetch_add(1, Ordering::Relaxed);
+    let type_id = id!(T);
+    layers()
+        .write()
+        .typed
+        .entry(type_id)
+        .or_default()
+        .push((id, Arc::new(layer)));
+    LAYERS_COUNT.fetch_add(1, Ordering::Release);
+    LayerHandle {
+        id,
+        type_id: Some(type_id),
+    }
+}
+
+type Entries = Vec<(usize, Arc<dyn Layer>)>;
+
+#[derive(Default)]
+struct Layers {
+    global: Entries,
+    typed: HashMap<TypeId, Entries>,
+}
+
+fn layers() -> &'static RwLock<Layers> {
+    LAYERS.get_or_init(Default::default)
+}
+
+/// Passes the message of the type `T` through the registered layers
+pub(crate) fn apply<T: 'static, P: 'static>(
+    kind: Kind,
+    stage: Stage,
+    name: &'static str,
+    topic: Option<&str>,
+    payload: &mut P,
+) -> Result<(), String> {
+    if LAYERS_COUNT.load(Ordering::Acquire) == 0 {
+        return Ok(());
+    }
+    let type_id = id!(T);
+    // layers are called without the lock,
+    // so they are able to add or remove layers
+    let chain: Vec<Arc<dyn Layer>> = {
+        let layers = layers().read();
+        let typed = layers.typed.get(&type_id).into_iter().flatten();
+        layers
+            .global
+            .iter()
+            .chain(typed)
+            .map(|(_, layer)| layer.clone())
+            .collect()
+    };
+    let mut message = Message {
+        kind,
+        stage,
+        type_id,
+        name,
+        topic,
+        payload,
+    };
+    for layer in chain {
+        match stage {
+            Stage::Send => layer.on_send(&mut message)?,
+            Stage::Receive => layer.on_receive(&mut message)?,
+        }
+    }
+    Ok(())
+}
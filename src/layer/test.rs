@@ -0,0 +1,161 @@
+use super::*;
+use crate::{
+    broadcast::{self, Broadcast},
+    notification::{self, Notification, NotifyError},
+    request::{self, Request, RequestError},
+};
+use std::{convert::Infallible, sync::atomic::AtomicUsize};
+
+struct LayerRequest;
+struct LayerNotification;
+struct LayerBroadcast;
+
+impl Request for LayerRequest {
+    type Payload = i32;
+    type Response = i32;
+    type Error = Infallible;
+    const BUFFER_SIZE: usize = 0;
+    const DEBUG_NAME: &'static str = "LayerRequest";
+}
+
+impl Notification for LayerNotification {
+    type Payload = i32;
+    const BUFFER_SIZE: usize = 0;
+    const DEBUG_NAME: &'static str = "LayerNotification";
+}
+
+impl Broadcast for LayerBroadcast {
+    type Payload = i32;
+    const BUFFER_SIZE: usize = 4;
+    const DEBUG_NAME: &'static str = "LayerBroadcast";
+}
+
+/// Rejects negative payloads on send
+struct Validate;
+
+impl Layer for Validate {
+    fn on_send(&self, message: &mut Message<'_>) -> Result<(), String> {
+        match message.payload::<i32>() {
+            Some(n) if *n < 0 => Err(format!("{} is negative", n)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Doubles payloads on receive
+struct Double;
+
+impl Layer for Double {
+    fn on_receive(&self, message: &mut Message<'_>) -> Result<(), String> {
+        if let Some(n) = message.payload_mut::<i32>() {
+            *n *= 2;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the messages of the layer test types
+struct Count(Arc<AtomicUsize>);
+
+impl Layer for Count {
+    fn on_send(&self, message: &mut Message<'_>) -> Result<(), String> {
+        if message.is::<LayerRequest>() || message.is::<LayerNotification>() {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn request_layers() {
+    println!("request_layers: Add layers");
+    let validate = add_layer_for::<LayerRequest>(Validate);
+    let double = add_layer_for::<LayerRequest>(Double);
+    let sent = Arc::new(AtomicUsize::new(0));
+    let count = add_layer(Count(sent.clone()));
+
+    let mut listener = request::listen::<LayerRequest>().await.unwrap();
+    let h = tokio::spawn(async move {
+        listener.accept(|n| async move { n + 1 }).await;
+        listener
+    });
+
+    println!("request_layers: Rejected on send");
+    match request::request::<LayerRequest>(-1).await {
+        Err(RequestError::Rejected { reason }) => assert_eq!(reason, "-1 is negative"),
+        r => panic!("Unexpected result: {:?}", r.map(|_| ())),
+    }
+    match request::request_keyed::<LayerRequest, _>(&"key", -2).await {
+        Err(RequestError::Rejected { reason }) => assert_eq!(reason, "-2 is negative"),
+        r => panic!("Unexpected result: {:?}", r.map(|_| ())),
+    }
+
+    println!("request_layers: Modified on receive");
+    assert_eq!(request::request::<LayerRequest>(2).await.unwrap(), 5);
+    assert_eq!(sent.load(Ordering::Relaxed), 3);
+    h.await.unwrap().close().await;
+
+    println!("request_layers: Remove layers");
+    validate.remove();
+    double.remove();
+    count.remove();
+    let mut listener = request::listen::<LayerRequest>().await.unwrap();
+    let h = tokio::spawn(async move {
+        listener.accept(|n| async move { n + 1 }).await;
+        listener
+    });
+    assert_eq!(request::request::<LayerRequest>(-1).await.unwrap(), 0);
+    h.await.unwrap().close().await;
+}
+
+#[tokio::test]
+async fn notification_layers() {
+    println!("notification_layers: Add layers");
+    let validate = add_layer_for::<LayerNotification>(Validate);
+    let double = add_layer_for::<LayerNotification>(Double);
+    let mut subscription = notification::subscribe::<LayerNotification>()
+        .await
+        .unwrap();
+
+    println!("notification_layers: Rejected on send");
+    assert!(matches!(
+        notification::notify::<LayerNotification>(-1).await,
+        Err(NotifyError::Rejected { .. })
+    ));
+
+    println!("notification_layers: Modified on receive");
+    notification::notify::<LayerNotification>(3).await.unwrap();
+    assert_eq!(subscription.recv().await, 6);
+    subscription.close().await;
+    validate.remove();
+    double.remove();
+}
+
+/// Skips odd payloads on receive
+struct SkipOdd;
+
+impl Layer for SkipOdd {
+    fn on_receive(&self, message: &mut Message<'_>) -> Result<(), String> {
+        match message.payload::<i32>() {
+            Some(n) if n % 2 != 0 => Err("odd".into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn broadcast_layers() {
+    println!("broadcast_layers: Add layers");
+    let validate = add_layer_for::<LayerBroadcast>(Validate);
+    let skip_odd = add_layer_for::<LayerBroadcast>(SkipOdd);
+    let mut subscription = broadcast::subscribe::<LayerBroadcast>().await;
+
+    println!("broadcast_layers: Dropped on send and skipped on receive");
+    broadcast::notify::<LayerBroadcast>(-2).await;
+    broadcast::notify::<LayerBroadcast>(1).await;
+    broadcast::notify::<LayerBroadcast>(2).await;
+    assert_eq!(subscription.recv().await, 2);
+    subscription.close().await;
+    validate.remove();
+    skip_odd.remove();
+}
@@ -7,20 +7,18 @@
 //! ## Example
 //!
 //! ```rust
-//! use intercomm::{broadcast, notification, request};
+//! use intercomm::{broadcast, request};
 //!
 //! intercomm::declare! {
 //!     request Sum((i32, i32)) -> i32;
 //!     request Mul((i32, i32)) -> i32;
 //!
-//!     notification[2] Ready(());
 //!     broadcast[1] Close(());
 //! }
 //!
 //! async fn sum_listener() {
-//!     let mut listener = request::listen::<Sum>().await.expect("Sum listen twice");
 //!     let mut close = broadcast::subscribe::<Close>().await;
-//!     Ready::notify(()).await.expect("Cannot send Ready");
+//!     let mut listener = request::listen::<Sum>().await.expect("Sum listen twice");
 //!
 //!     loop {
 //!         tokio::select! {
@@ -38,9 +36,8 @@
 //! }
 //!
 //! async fn mul_listener() {
-//!     let mut listener = request::listen::<Mul>().await.expect("Mul listen twice");
 //!     let mut close = broadcast::subscribe::<Close>().await;
-//!     Ready::notify(()).await.expect("Cannot send Ready");
+//!     let mut listener = request::listen::<Mul>().await.expect("Mul listen twice");
 //!
 //!     loop {
 //!         tokio::select! {
@@ -59,15 +56,10 @@
 //!
 //! #[tokio::main]
 //! async fn main() {
-//!     let mut ready = notification::subscribe::<Ready>()
-//!         .await
-//!         .expect("Ready subscribed twice");
 //!     let sum_join = tokio::spawn(sum_listener());
 //!     let mul_join = tokio::spawn(mul_listener());
-//!     for _ in 0..2 {
-//!         ready.recv().await;
-//!     }
-//!     ready.close().await;
+//!     request::wait_listener::<Sum>().await;
+//!     request::wait_listener::<Mul>().await;
 //!
 //!     let sum = Sum::request((5, 10)).await.expect("Cannot request Sum");
 //!     println!("5 + 10 = {}", sum);
@@ -85,26 +77,67 @@
 mod common;
 
 pub mod broadcast;
+pub mod layer;
 pub mod notification;
+pub mod priority;
+pub mod rate;
 pub mod request;
+pub mod retry;
+pub mod schedule;
+pub mod session;
+pub mod stream_request;
 
 /// Declare types for
 /// [Broadcast](crate::broadcast::Broadcast),
 /// [Notification](crate::notification::Notification),
-/// [Request](crate::request::Request)
+/// [Request](crate::request::Request),
+/// [StreamRequest](crate::stream_request::StreamRequest),
+/// [Session](crate::session::Session)
 ///
 /// ## Syntax
 ///
 /// `<visibility>? broadcast[<buffer size>] <name>(<payload type>);` \
-/// `<visibility>? notification[<buffer size>] <name>(<payload type>);` \
+/// `<visibility>? notification[<buffer size>] <name>(<payload type>) with <options>;` \
 /// `<visibility>? request[<buffer size>] <name>(<payload type>) -> <response type>;` \
+/// `<visibility>? request[<buffer size>] <name>(<payload type>) -> <response type> throws <error type> with <options>;` \
+/// `<visibility>? stream_request[<buffer size>, <stream buffer size>] <name>(<payload type>) -> <item type>;` \
+/// `<visibility>? session[<buffer size>] <name>(<in message type>) <-> <out message type>;` \
+///
+/// `<buffer size>` is optional for `notification`, `request` & `stream_request` and required for `broadcast`
+///
+/// `<stream buffer size>` is optional for `stream_request`, it is 1 by default
+///
+/// `<buffer size>` is optional for `session`, it is 1 by default
 ///
-/// `<buffer size>` is optional for `notification` & `request` and required for `broadcast`
+/// `with <options>` is optional for `notification` & `request`,
+/// `<options>` are separated by commas:
+/// - `retry(<policy>)` - retry by the [RetryPolicy](crate::retry::RetryPolicy)
+///   while there is no listener or subscriber
+/// - `starvation_limit(<limit>)` - receive one lower
+///   [Priority](crate::priority::Priority) item after `<limit>` higher ones in a row
+/// - `ttl(<duration>)` - skip the payloads queued longer than `<duration>`,
+///   the expired requests fail with [RequestError::Expired](crate::request::RequestError::Expired)
+/// - `overflow(<policy>)` - handle the full buffer by the
+///   [Overflow](crate::notification::Overflow) policy, `notification` only
+/// - `dedup_by_key(<key fn>)` - keep only the latest queued payload per key,
+///   see [Notification::DEDUP](crate::notification::Notification::DEDUP), `notification` only
+/// - `dedup` - keep only the latest queued payload, `notification` only
+/// - `single_flight` - collapse the concurrent identical requests into one,
+///   see [single_flight](crate::request::single_flight), `request` only
+/// - `cached(<ttl>, <capacity>)` - respond from the cache of up to `<capacity>`
+///   responses kept for `<ttl>`, see [cached](crate::request::cached), `request` only
+/// - `circuit_breaker(<breaker>)` - fail fast by the
+///   [CircuitBreaker](crate::request::CircuitBreaker), `request` only
+///
+/// `<error type>` is optional for `request`, if omitted the request can not fail in the listener
 ///
 ///
 /// ## Example
 ///
 /// ```rust
+/// use intercomm::{notification::Overflow, request::CircuitBreaker, retry::RetryPolicy};
+/// use std::time::Duration;
+///
 /// intercomm::declare! {
 ///    /// B1 broadcast
 ///    broadcast[4] B1(i32);
@@ -119,6 +152,18 @@ pub mod request;
 ///    pub(crate) notification[1] N2(i32);
 ///    /// N3 notification
 ///    pub notification[4] N3(i32);
+///    /// N4 notification
+///    pub notification N4(i32) with retry(RetryPolicy::fixed(Duration::from_millis(10)));
+///    /// N5 notification
+///    pub notification N5(i32) with starvation_limit(8), ttl(Duration::from_secs(1));
+///    /// N6 notification
+///    pub notification[16] N6(i32) with overflow(Overflow::DropOldest);
+///    /// N7 notification
+///    pub notification[1] N7(i32) with overflow(Overflow::Coalesce(|last, n| *last += n));
+///    /// N8 notification
+///    pub notification N8((u64, String)) with dedup_by_key(|(id, _)| *id);
+///    /// N9 notification
+///    pub notification N9(()) with dedup;
 ///
 ///    /// R1 request
 ///    request R1((i32, i32)) -> i32;
@@ -126,6 +171,34 @@ pub mod request;
 ///    pub(crate) request[4] R2((i32, i32)) -> i32;
 ///    /// R3 request
 ///    pub request[4] R3((i32, i32)) -> i32;
+///    /// R4 request
+///    pub request R4((i32, i32)) -> i32 throws String;
+///    /// R5 request
+///    pub request R5((i32, i32)) -> i32 throws String with
+///        retry(RetryPolicy::exponential(Duration::from_millis(10)).with_max_attempts(5));
+///    /// R6 request
+///    pub request R6(String) -> String with single_flight;
+///    /// R7 request
+///    pub request R7(String) -> String with cached(Duration::from_secs(60), 100), single_flight;
+///    /// R8 request
+///    pub request R8(String) -> String throws String with
+///        circuit_breaker(CircuitBreaker::new(5, Duration::from_secs(10)));
+///    /// R9 request
+///    pub request[16] R9(String) -> String with ttl(Duration::from_millis(500));
+///
+///    /// S1 stream request
+///    stream_request S1(i32) -> i32;
+///    /// S2 stream request
+///    pub(crate) stream_request[4] S2(i32) -> i32;
+///    /// S3 stream request
+///    pub stream_request[4, 16] S3(i32) -> i32;
+///
+///    /// C1 session
+///    session C1(i32) <-> String;
+///    /// C2 session
+///    pub(crate) session[4] C2(i32) <-> String;
+///    /// C3 session
+///    pub session[16] C3(i32) <-> String;
 /// }
 /// ```
 #[macro_export]
@@ -151,6 +224,11 @@ macro_rules! declare {
             $v async fn notify(payload: $payload) {
                 $crate::broadcast::notify::<$name>(payload).await
             }
+
+            /// Sends a payload with the topic to the TopicSubscriptions
+            $v async fn notify_topic(topic: &str, payload: $payload) {
+                $crate::broadcast::notify_topic::<$name>(topic, payload).await
+            }
         }
 
         $crate::declare!($($next)*);
@@ -158,7 +236,8 @@ macro_rules! declare {
 
     (
         $(#[$attr:meta])*
-        $v:vis notification $([$buffer_size:expr])? $name:ident ($payload:ty);
+        $v:vis notification $([$buffer_size:expr])? $name:ident ($payload:ty)
+        $(with $($option:ident $(($($args:tt)*))?),+)?;
         $($next:tt)*
     ) => {
         $(#[$attr])*
@@ -168,6 +247,7 @@ macro_rules! declare {
             type Payload = $payload;
             const BUFFER_SIZE: usize = $crate::declare!(@buffer-size $($buffer_size)?);
             const DEBUG_NAME: &'static str = stringify!($name);
+            $($($crate::declare!(@notification-option $option [$($($args)*)?]);)+)?
         }
 
         impl $name {
@@ -184,6 +264,136 @@ macro_rules! declare {
         $(#[$attr:meta])*
         $v:vis request $([$buffer_size:expr])? $name:ident ($payload:ty) -> $response:ty;
         $($next:tt)*
+    ) => {
+        $crate::declare!(
+            @request [[$(#[$attr])*] [$v] [$($buffer_size)?] $name ($payload)]
+            [$response] [::std::convert::Infallible] []
+        );
+
+        $crate::declare!($($next)*);
+    };
+
+    (
+        $(#[$attr:meta])*
+        $v:vis request $([$buffer_size:expr])? $name:ident ($payload:ty) -> $($rest:tt)+
+    ) => {
+        $crate::declare!(
+            @request-response [[$(#[$attr])*] [$v] [$($buffer_size)?] $name ($payload)]
+            [] $($rest)+
+        );
+    };
+
+    (
+        $(#[$attr:meta])*
+        $v:vis stream_request $([$buffer_size:expr $(, $stream_buffer_size:expr)?])? $name:ident ($payload:ty) -> $item:ty;
+        $($next:tt)*
+    ) => {
+        $(#[$attr])*
+        $v struct $name;
+
+        impl $crate::stream_request::StreamRequest for $name {
+            type Payload = $payload;
+            type Item = $item;
+            const BUFFER_SIZE: usize = $crate::declare!(@buffer-size $($buffer_size)?);
+            const STREAM_BUFFER_SIZE: usize = $crate::declare!(@stream-buffer-size $($($stream_buffer_size)?)?);
+            const DEBUG_NAME: &'static str = stringify!($name);
+        }
+
+        impl $name {
+            /// Sends a payload to the Listener
+            $v async fn request(
+                payload: $payload,
+            ) -> Result<
+                $crate::stream_request::ResponseStream<$name>,
+                $crate::stream_request::StreamRequestError<$name>,
+            > {
+                $crate::stream_request::request::<$name>(payload).await
+            }
+        }
+
+        $crate::declare!($($next)*);
+    };
+
+    (
+        $(#[$attr:meta])*
+        $v:vis session $([$buffer_size:expr])? $name:ident ($in:ty) <-> $out:ty;
+        $($next:tt)*
+    ) => {
+        $(#[$attr])*
+        $v struct $name;
+
+        impl $crate::session::Session for $name {
+            type In = $in;
+            type Out = $out;
+            const BUFFER_SIZE: usize = $crate::declare!(@stream-buffer-size $($buffer_size)?);
+            const DEBUG_NAME: &'static str = stringify!($name);
+        }
+
+        impl $name {
+            /// Opens a session with the Listener
+            $v async fn open() -> Result<$crate::session::Endpoint<$in, $out>, $crate::session::SessionError> {
+                $crate::session::open::<$name>().await
+            }
+        }
+
+        $crate::declare!($($next)*);
+    };
+
+    (
+        @request-response $head:tt [$($response:tt)+] throws $($rest:tt)+
+    ) => {
+        $crate::declare!(@request-error $head [$($response)+] [] $($rest)+);
+    };
+
+    (
+        @request-response $head:tt [$($response:tt)+] with $($rest:tt)+
+    ) => {
+        $crate::declare!(
+            @request-options $head [$($response)+] [::std::convert::Infallible]
+            with $($rest)+
+        );
+    };
+
+    (
+        @request-response $head:tt [$($response:tt)*] $token:tt $($rest:tt)*
+    ) => {
+        $crate::declare!(@request-response $head [$($response)* $token] $($rest)*);
+    };
+
+    (
+        @request-error $head:tt $response:tt [$($error:tt)+] with $($rest:tt)+
+    ) => {
+        $crate::declare!(@request-options $head $response [$($error)+] with $($rest)+);
+    };
+
+    (
+        @request-error $head:tt $response:tt [$($error:tt)+];
+        $($next:tt)*
+    ) => {
+        $crate::declare!(@request $head $response [$($error)+] []);
+
+        $crate::declare!($($next)*);
+    };
+
+    (
+        @request-error $head:tt $response:tt [$($error:tt)*] $token:tt $($rest:tt)*
+    ) => {
+        $crate::declare!(@request-error $head $response [$($error)* $token] $($rest)*);
+    };
+
+    (
+        @request-options $head:tt $response:tt $error:tt
+        with $($option:ident $(($($args:tt)*))?),+;
+        $($next:tt)*
+    ) => {
+        $crate::declare!(@request $head $response $error [$($option [$($($args)*)?])+]);
+
+        $crate::declare!($($next)*);
+    };
+
+    (
+        @request [[$(#[$attr:meta])*] [$v:vis] [$($buffer_size:expr)?] $name:ident ($payload:ty)]
+        [$response:ty] [$error:ty] [$($option:ident $args:tt)*]
     ) => {
         $(#[$attr])*
         $v struct $name;
@@ -191,8 +401,10 @@ macro_rules! declare {
         impl $crate::request::Request for $name {
             type Payload = $payload;
             type Response = $response;
+            type Error = $error;
             const BUFFER_SIZE: usize = $crate::declare!(@buffer-size $($buffer_size)?);
             const DEBUG_NAME: &'static str = stringify!($name);
+            $($crate::declare!(@request-option $option $args);)*
         }
 
         impl $name {
@@ -201,10 +413,61 @@ macro_rules! declare {
                 $crate::request::request::<$name>(payload).await
             }
         }
+    };
 
-        $crate::declare!($($next)*);
+    (@request-option retry [$policy:expr]) => {
+        const RETRY: Option<$crate::retry::RetryPolicy> = Some($policy);
+    };
+
+    (@request-option single_flight []) => {
+        const SINGLE_FLIGHT: Option<$crate::request::SingleFlight<Self>> =
+            Some($crate::request::single_flight::<Self>);
+    };
+
+    (@request-option cached [$ttl:expr, $capacity:expr]) => {
+        const CACHE: Option<$crate::request::Cache<Self>> =
+            Some($crate::request::Cache::new($ttl, $capacity, $crate::request::cached::<Self>));
+    };
+
+    (@request-option circuit_breaker [$breaker:expr]) => {
+        const CIRCUIT_BREAKER: Option<$crate::request::CircuitBreaker> = Some($breaker);
+    };
+
+    (@request-option starvation_limit [$limit:expr]) => {
+        const STARVATION_LIMIT: Option<u32> = Some($limit);
+    };
+
+    (@request-option ttl [$ttl:expr]) => {
+        const TTL: Option<::std::time::Duration> = Some($ttl);
+    };
+
+    (@notification-option retry [$policy:expr]) => {
+        const RETRY: Option<$crate::retry::RetryPolicy> = Some($policy);
+    };
+
+    (@notification-option starvation_limit [$limit:expr]) => {
+        const STARVATION_LIMIT: Option<u32> = Some($limit);
+    };
+
+    (@notification-option ttl [$ttl:expr]) => {
+        const TTL: Option<::std::time::Duration> = Some($ttl);
+    };
+
+    (@notification-option overflow [$overflow:expr]) => {
+        const OVERFLOW: $crate::notification::Overflow<Self::Payload> = $overflow;
+    };
+
+    (@notification-option dedup_by_key [$key:expr]) => {
+        const DEDUP: Option<fn(&Self::Payload) -> u64> = Some($key);
+    };
+
+    (@notification-option dedup []) => {
+        const DEDUP: Option<fn(&Self::Payload) -> u64> = Some(|_| 0);
     };
 
     (@buffer-size) => { 0 };
     (@buffer-size $buffer_size:expr) => { $buffer_size };
+
+    (@stream-buffer-size) => { 1 };
+    (@stream-buffer-size $stream_buffer_size:expr) => { $stream_buffer_size };
 }
@@ -2,17 +2,28 @@
 //!
 //! Notifications with one subscriber per time
 
-use crate::common::StaticTypeMap;
-use tokio::sync::mpsc::{error::SendError, Sender, UnboundedSender};
+use crate::{
+    common::{expires_at, ExpiryCounters, Queue, StaticTypeMap, TryPushError},
+    layer::{self, Kind, Stage},
+    priority::Priority,
+    retry::RetryPolicy,
+};
+use std::{sync::Arc, time::Duration};
+use tokio::time::Instant;
 
+mod overflow;
+mod schedule;
 mod subscription;
 
 #[cfg(test)]
 mod test;
 
+pub use overflow::*;
+pub use schedule::*;
 pub use subscription::*;
 
 static CHANNELS: StaticTypeMap = StaticTypeMap::new();
+static EXPIRED: ExpiryCounters = ExpiryCounters::new();
 
 /// A multi-notifier, single-subscriber notification
 pub trait Notification: Sized + 'static {
@@ -26,7 +37,35 @@ pub trait Notification: Sized + 'static {
     const DEBUG_NAME: &'static str;
 
     /// Payload data type that will be sended with this notification
-    type Payload: Send;
+    type Payload: Send + 'static;
+
+    /// Policy of retrying the [notify] while it is not subscribed
+    const RETRY: Option<RetryPolicy> = None;
+
+    /// The number of the higher priority notifications received
+    /// while a lower priority one is waiting,
+    /// after that the lower priority notification is received,
+    /// the lane waiting the longest goes first
+    ///
+    /// None or `Some(0)` to always receive the higher priority first
+    const STARVATION_LIMIT: Option<u32> = None;
+
+    /// Policy of the [notify] to the full subscription buffer
+    const OVERFLOW: Overflow<Self::Payload> = Overflow::Block;
+
+    /// Key of the queued payloads, the sent payload replaces
+    /// the queued one with the same key in its place
+    /// and takes its slot in the subscription buffer
+    ///
+    /// The constant key collapses the queued payloads into the latest one,
+    /// None to queue every payload
+    const DEDUP: Option<fn(&Self::Payload) -> u64> = None;
+
+    /// Time to live of the queued payloads,
+    /// the payloads not received in time are skipped
+    ///
+    /// None to keep the payloads queued until they are received
+    const TTL: Option<Duration> = None;
 }
 
 /// This enumeration is the list of the possible error outcomes for the
@@ -37,29 +76,311 @@ pub enum NotifyError<N: Notification> {
     NotSubscribed(N::Payload),
     /// Internal notification channel is closed
     SendError(N::Payload),
+    /// Subscription buffer is full,
+    /// see [Overflow::Error]
+    Full(N::Payload),
+    /// Notification is rejected by a [Layer](crate::layer::Layer)
+    Rejected {
+        /// The reason of the rejection
+        reason: String,
+    },
+}
+
+/// Error of the [notify_all](crate::notification::notify_all) fn
+pub struct NotifyAllError<N: Notification> {
+    /// The number of the payloads accepted before the failure
+    pub accepted: usize,
+    /// The error of the failed payload
+    pub error: NotifyError<N>,
+    /// The payloads after the failed one, they are not sent
+    pub rest: Vec<N::Payload>,
 }
 
 /// Sends a payload to the [Subscription](crate::notification::Subscription)
+///
+/// Retries by [Notification::RETRY] policy if it is set
 pub async fn notify<N: Notification>(payload: N::Payload) -> Result<(), NotifyError<N>> {
-    let id = id!(N);
-    let channels = CHANNELS.read().await;
-    let sender = match channels.get(&id) {
-        Some(sender) => sender,
+    notify_with_priority::<N>(payload, Priority::Normal).await
+}
+
+/// Sends a payload with the priority
+/// to the [Subscription](crate::notification::Subscription)
+///
+/// Higher priority notifications are received first
+///
+/// Retries by [Notification::RETRY] policy if it is set
+pub async fn notify_with_priority<N: Notification>(
+    payload: N::Payload,
+    priority: Priority,
+) -> Result<(), NotifyError<N>> {
+    let mut payload = payload;
+    intercept::<N>(Stage::Send, &mut payload)?;
+    match N::RETRY {
+        Some(policy) => retry_notify::<N>(payload, &policy, priority, N::TTL).await,
+        None => notify_once::<N>(payload, priority, N::TTL).await,
+    }
+}
+
+/// Sends a payload to the [Subscription](crate::notification::Subscription)
+///
+/// Retries by the `policy` while the notification is not subscribed
+/// or its subscription is closed
+pub async fn notify_with_retry<N: Notification>(
+    payload: N::Payload,
+    policy: &RetryPolicy,
+) -> Result<(), NotifyError<N>> {
+    let mut payload = payload;
+    intercept::<N>(Stage::Send, &mut payload)?;
+    retry_notify::<N>(payload, policy, Priority::Normal, N::TTL).await
+}
+
+/// Sends a payload with the time to live
+/// to the [Subscription](crate::notification::Subscription)
+///
+/// The payload not received in `ttl` is skipped
+///
+/// Unlike [notify] ignores [Notification::TTL]
+///
+/// Retries by [Notification::RETRY] policy if it is set
+pub async fn notify_with_ttl<N: Notification>(
+    payload: N::Payload,
+    ttl: Duration,
+) -> Result<(), NotifyError<N>> {
+    let mut payload = payload;
+    intercept::<N>(Stage::Send, &mut payload)?;
+    match N::RETRY {
+        Some(policy) => retry_notify::<N>(payload, &policy, Priority::Normal, Some(ttl)).await,
+        None => notify_once::<N>(payload, Priority::Normal, Some(ttl)).await,
+    }
+}
+
+/// Sends the payloads to the [Subscription](crate::notification::Subscription)
+/// with one lookup of the subscription
+///
+/// The payloads are sent in order until the first failure,
+/// the [Overflow::Block] payloads are enqueued in bulk
+/// of up to [Notification::BUFFER_SIZE]
+///
+/// Returns the number of the accepted payloads
+///
+/// Unlike [notify] ignores [Notification::RETRY]
+pub async fn notify_all<N, I>(payloads: I) -> Result<usize, NotifyAllError<N>>
+where
+    N: Notification,
+    I: IntoIterator<Item = N::Payload>,
+{
+    let mut payloads = payloads.into_iter();
+    let queue = match subscribed::<N>().await {
+        Some(queue) => queue,
+        None => {
+            return match payloads.next() {
+                Some(payload) => Err(NotifyAllError {
+                    accepted: 0,
+                    error: NotifyError::NotSubscribed(payload),
+                    rest: payloads.collect(),
+                }),
+                None => Ok(0),
+            }
+        }
+    };
+    let expires = expires_at(N::TTL);
+    let batch_size = N::BUFFER_SIZE.max(1);
+    let mut accepted = 0;
+    loop {
+        let mut batch = Vec::new();
+        let mut failure = None;
+        let mut done = true;
+        for mut payload in payloads.by_ref() {
+            let result = match intercept::<N>(Stage::Send, &mut payload) {
+                Err(error) => Err(error),
+                // the blocking payloads are enqueued in bulk
+                Ok(()) if matches!(N::OVERFLOW, Overflow::Block) => {
+                    batch.push(Queued { payload, expires });
+                    if batch.len() < batch_size {
+                        continue;
+                    }
+                    done = false;
+                    break;
+                }
+                Ok(()) => enqueue::<N>(&queue, Queued { payload, expires }, Priority::Normal).await,
+            };
+            match result {
+                Ok(()) => accepted += 1,
+                Err(error) => {
+                    failure = Some(error);
+                    break;
+                }
+            }
+        }
+        if !batch.is_empty() {
+            let count = batch.len();
+            let unsent = queue
+                .push_all(batch, Priority::Normal)
+                .await
+                .err()
+                .unwrap_or_default();
+            accepted += count - unsent.len();
+            let mut unsent = unsent.into_iter().map(|queued| queued.payload);
+            if let Some(payload) = unsent.next() {
+                return Err(NotifyAllError {
+                    accepted,
+                    error: NotifyError::SendError(payload),
+                    rest: unsent.chain(payloads).collect(),
+                });
+            }
+        }
+        match failure {
+            Some(error) => {
+                return Err(NotifyAllError {
+                    accepted,
+                    error,
+                    rest: payloads.collect(),
+                })
+            }
+            None if done => return Ok(accepted),
+            None => {}
+        }
+    }
+}
+
+/// Returns the number of the payloads expired in the subscription queue
+///
+/// See [Notification::TTL] and [notify_with_ttl]
+pub fn expired_count<N: Notification>() -> u64 {
+    EXPIRED.get(id!(N))
+}
+
+async fn retry_notify<N: Notification>(
+    payload: N::Payload,
+    policy: &RetryPolicy,
+    priority: Priority,
+    ttl: Option<Duration>,
+) -> Result<(), NotifyError<N>> {
+    let mut payload = payload;
+    let mut retry = policy.start();
+    loop {
+        let result = notify_once::<N>(payload, priority, ttl).await;
+        let backoff = match &result {
+            Err(_) => retry.next_backoff(),
+            Ok(()) => None,
+        };
+        match (result, backoff) {
+            (Err(NotifyError::NotSubscribed(p)), Some(backoff))
+            | (Err(NotifyError::SendError(p)), Some(backoff)) => {
+                payload = p;
+                tokio::time::sleep(backoff).await;
+            }
+            (result, _) => return result,
+        }
+    }
+}
+
+/// Passes the payload through the [layers](crate::layer)
+fn intercept<N: Notification>(
+    stage: Stage,
+    payload: &mut N::Payload,
+) -> Result<(), NotifyError<N>> {
+    layer::apply::<N, _>(Kind::Notification, stage, N::DEBUG_NAME, None, payload)
+        .map_err(|reason| NotifyError::Rejected { reason })
+}
+
+async fn notify_once<N: Notification>(
+    payload: N::Payload,
+    priority: Priority,
+    ttl: Option<Duration>,
+) -> Result<(), NotifyError<N>> {
+    let queue = match subscribed::<N>().await {
+        Some(queue) => queue,
         None => return Err(NotifyError::NotSubscribed(payload)),
     };
-    if N::BUFFER_SIZE == 0 {
-        let sender: &UnboundedSender<_> = unsafe { sender.get_ref() };
-        sender.send(payload)?
-    } else {
-        let sender: &Sender<_> = unsafe { sender.get_ref() };
-        sender.send(payload).await?
+    let queued = Queued {
+        payload,
+        expires: expires_at(ttl),
+    };
+    enqueue::<N>(&queue, queued, priority).await
+}
+
+/// Returns the queue of the subscription,
+/// the channels are not locked while the queue is full
+async fn subscribed<N: Notification>() -> Option<Arc<NotificationQueue<N>>> {
+    let channels = CHANNELS.read().await;
+    let queue: &Arc<NotificationQueue<N>> = unsafe { channels.get(&id!(N))?.get_ref() };
+    Some(queue.clone())
+}
+
+/// Pushes the payload to the queue by [Notification::OVERFLOW] policy
+async fn enqueue<N: Notification>(
+    queue: &NotificationQueue<N>,
+    queued: Queued<N::Payload>,
+    priority: Priority,
+) -> Result<(), NotifyError<N>> {
+    let result = match N::OVERFLOW {
+        Overflow::Block => queue.push(queued, priority).await,
+        Overflow::DropNewest => match queue.try_push(queued, priority) {
+            Err(TryPushError::Closed(queued)) => Err(queued),
+            _ => Ok(()),
+        },
+        Overflow::DropOldest => queue.push_dropping_oldest(queued, priority).await,
+        Overflow::Error => match queue.try_push(queued, priority) {
+            Err(TryPushError::Full(queued)) => return Err(NotifyError::Full(queued.payload)),
+            Err(TryPushError::Closed(queued)) => Err(queued),
+            Ok(()) => Ok(()),
+        },
+        Overflow::Coalesce(_) => queue.push_merging(queued, priority, coalesce::<N>).await,
+    };
+    result.map_err(|queued| NotifyError::SendError(queued.payload))
+}
+
+/// Payload queued in the subscription
+struct Queued<P> {
+    payload: P,
+    /// None if the payload never expires
+    expires: Option<Instant>,
+}
+
+type NotificationQueue<N> = Queue<Queued<<N as Notification>::Payload>>;
+
+/// Key of the queued payload by [Notification::DEDUP]
+fn dedup_key<N: Notification>(queued: &Queued<N::Payload>) -> u64 {
+    N::DEDUP.map_or(0, |key| key(&queued.payload))
+}
+
+/// Merges the payload into the queued one by [Overflow::Coalesce]
+fn coalesce<N: Notification>(last: &mut Queued<N::Payload>, next: Queued<N::Payload>) {
+    if let Overflow::Coalesce(merge) = N::OVERFLOW {
+        merge(&mut last.payload, next.payload);
+        last.expires = next.expires;
     }
-    Ok(())
 }
 
-impl<N: Notification> From<SendError<N::Payload>> for NotifyError<N> {
-    fn from(e: SendError<N::Payload>) -> Self {
-        NotifyError::SendError(e.0)
+/// Waits until the notification is subscribed
+pub async fn wait_subscriber<N: Notification>() {
+    CHANNELS
+        .wait_until(id!(N), |queue| {
+            let queue: &Arc<NotificationQueue<N>> = unsafe { queue.get_ref() };
+            !queue.is_closed()
+        })
+        .await
+}
+
+/// Waits until the notification is subscribed
+///
+/// Returns false if the notification is not subscribed in `timeout`
+pub async fn wait_subscriber_timeout<N: Notification>(timeout: Duration) -> bool {
+    tokio::time::timeout(timeout, wait_subscriber::<N>())
+        .await
+        .is_ok()
+}
+
+impl<N: Notification> std::fmt::Debug for NotifyAllError<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "NotifyAllError in {}: {:?} after {} accepted",
+            N::DEBUG_NAME,
+            self.error,
+            self.accepted
+        )
     }
 }
 
@@ -72,6 +393,12 @@ impl<N: Notification> std::fmt::Debug for NotifyError<N> {
             NotifyError::SendError(_) => {
                 write!(f, "NotifyError in {}: SendError", N::DEBUG_NAME)?;
             }
+            NotifyError::Full(_) => {
+                write!(f, "NotifyError in {}: Full", N::DEBUG_NAME)?;
+            }
+            NotifyError::Rejected { reason } => {
+                write!(f, "NotifyError in {}: Rejected: {}", N::DEBUG_NAME, reason)?;
+            }
         }
         Ok(())
     }
@@ -0,0 +1,39 @@
+use std::fmt;
+
+/// Policy of the [notify](super::notify) to the full subscription buffer
+///
+/// Only bounded notifications (`BUFFER_SIZE > 0`) can overflow
+pub enum Overflow<P> {
+    /// Wait for the free space in the buffer
+    Block,
+    /// Drop the sent payload
+    DropNewest,
+    /// Drop the oldest queued payload of the lowest priority
+    DropOldest,
+    /// Fail with [NotifyError::Full](super::NotifyError::Full)
+    Error,
+    /// Merge the sent payload into the last queued one
+    /// of the same priority, or of the lowest priority
+    /// if there are no queued payloads of the same priority
+    Coalesce(fn(&mut P, P)),
+}
+
+impl<P> Clone for Overflow<P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P> Copy for Overflow<P> {}
+
+impl<P> fmt::Debug for Overflow<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Overflow::Block => write!(f, "Block"),
+            Overflow::DropNewest => write!(f, "DropNewest"),
+            Overflow::DropOldest => write!(f, "DropOldest"),
+            Overflow::Error => write!(f, "Error"),
+            Overflow::Coalesce(_) => write!(f, "Coalesce"),
+        }
+    }
+}
@@ -1,11 +1,15 @@
-use super::{Notification, CHANNELS};
-use crate::common::UntypedBox;
-use std::mem;
-use tokio::sync::mpsc::{channel, unbounded_channel, Receiver, UnboundedReceiver};
+use super::{dedup_key, intercept, Notification, NotificationQueue, Queued, CHANNELS, EXPIRED};
+use crate::{
+    common::{is_expired, Queue, UntypedBox},
+    layer::Stage,
+    rate::{Debounce, Sample, Throttle},
+};
+use std::{sync::Arc, time::Duration};
 
 /// Notification subscription
 pub struct Subscription<N: Notification> {
-    receiver: SubscriptionReceiver<N::Payload>,
+    /// None if the subscription is closed
+    queue: Option<Arc<NotificationQueue<N>>>,
 }
 
 /// Subscribe to notification
@@ -17,39 +21,83 @@ pub async fn subscribe<N: Notification>() -> Option<Subscription<N>> {
     if channels.contains_key(&id) {
         return None;
     }
-    let (sender, receiver) = if N::BUFFER_SIZE == 0 {
-        let (tx, rx) = unbounded_channel();
-        let tx = UntypedBox::new(tx);
-        let rx = SubscriptionReceiver::Unbounded(rx);
-        (tx, rx)
-    } else {
-        let (tx, rx) = channel(N::BUFFER_SIZE);
-        let tx = UntypedBox::new(tx);
-        let rx = SubscriptionReceiver::Bounded(rx);
-        (tx, rx)
-    };
-    channels.insert(id, sender);
-    Some(Subscription { receiver })
-}
-
-enum SubscriptionReceiver<Payload> {
-    Bounded(Receiver<Payload>),
-    Unbounded(UnboundedReceiver<Payload>),
-    Closed,
+    let queue = Queue::new(N::BUFFER_SIZE, N::STARVATION_LIMIT);
+    let queue = Arc::new(match N::DEDUP {
+        Some(_) => queue.with_dedup(dedup_key::<N>),
+        None => queue,
+    });
+    channels.insert(id, UntypedBox::new(queue.clone()));
+    Some(Subscription { queue: Some(queue) })
 }
 
 impl<N: Notification> Subscription<N> {
     /// Receives the next value for this Subscription
+    ///
+    /// Notifications rejected by a [Layer](crate::layer::Layer)
+    /// and the expired ones are skipped
     pub async fn recv(&mut self) -> N::Payload {
-        let payload = match &mut self.receiver {
-            SubscriptionReceiver::Bounded(rx) => rx.recv().await,
-            SubscriptionReceiver::Unbounded(rx) => rx.recv().await,
-            _ => unreachable!(),
+        let queue = match &self.queue {
+            Some(queue) => queue,
+            None => unreachable!(),
         };
-        match payload {
-            Some(payload) => payload,
+        loop {
+            let queued = match queue.pop().await {
+                Some(queued) => queued,
+                None => unreachable!(),
+            };
+            if let Some(payload) = receive::<N>(queued) {
+                return payload;
+            }
+        }
+    }
+
+    /// Receives up to `max` values for this Subscription into the `buffer`
+    ///
+    /// Waits for at least one value, then takes the queued ones
+    /// without waiting
+    ///
+    /// Returns the number of the received values, 0 if `max` is 0
+    pub async fn recv_many(&mut self, buffer: &mut Vec<N::Payload>, max: usize) -> usize {
+        if max == 0 {
+            return 0;
+        }
+        buffer.push(self.recv().await);
+        let queue = match &self.queue {
+            Some(queue) => queue,
             None => unreachable!(),
+        };
+        let mut count = 1;
+        while count < max {
+            let queued = match queue.try_pop() {
+                Some(queued) => queued,
+                None => break,
+            };
+            if let Some(payload) = receive::<N>(queued) {
+                buffer.push(payload);
+                count += 1;
+            }
         }
+        count
+    }
+
+    /// Adapts the subscription to receive a payload
+    /// only after no newer payloads are sent for the quiet `period`
+    pub fn debounce(self, period: Duration) -> Debounce<Self> {
+        Debounce::new(self, period)
+    }
+
+    /// Adapts the subscription to receive at most one payload per `period`
+    pub fn throttle(self, period: Duration) -> Throttle<Self> {
+        Throttle::new(self, period)
+    }
+
+    /// Adapts the subscription to receive the latest payload once per `period`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero
+    pub fn sample(self, period: Duration) -> Sample<Self> {
+        Sample::new(self, period)
     }
 
     /// Closes the subscription
@@ -57,22 +105,30 @@ impl<N: Notification> Subscription<N> {
     /// Closing the subscription with this method
     /// is preferable for performance reasons
     pub async fn close(mut self) {
-        let receiver = mem::replace(&mut self.receiver, SubscriptionReceiver::Closed);
-        match receiver {
-            SubscriptionReceiver::Bounded(mut rx) => rx.close(),
-            SubscriptionReceiver::Unbounded(mut rx) => rx.close(),
-            _ => unreachable!(),
+        match self.queue.take() {
+            Some(queue) => queue.close(),
+            None => unreachable!(),
         }
         CHANNELS.write().await.remove(&id!(N));
     }
 }
 
+/// Skips the expired and the rejected payload
+fn receive<N: Notification>(queued: Queued<N::Payload>) -> Option<N::Payload> {
+    if is_expired(queued.expires) {
+        EXPIRED.increment(id!(N));
+        return None;
+    }
+    let mut payload = queued.payload;
+    intercept::<N>(Stage::Receive, &mut payload).ok()?;
+    Some(payload)
+}
+
 impl<N: Notification> Drop for Subscription<N> {
     fn drop(&mut self) {
-        match &mut self.receiver {
-            SubscriptionReceiver::Bounded(rx) => rx.close(),
-            SubscriptionReceiver::Unbounded(rx) => rx.close(),
-            SubscriptionReceiver::Closed => return,
+        match self.queue.take() {
+            Some(queue) => queue.close(),
+            None => return,
         }
         CHANNELS.remove_when_possible(id!(N));
         #[cfg(debug_assertions)]
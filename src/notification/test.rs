@@ -110,3 +110,279 @@ async fn reopen_subscription() {
     println!("reopen_subscription: Join subscription #2");
     s2.await.unwrap();
 }
+
+struct Notification4;
+
+impl Notification for Notification4 {
+    type Payload = ();
+    const BUFFER_SIZE: usize = 0;
+    const DEBUG_NAME: &'static str = "Notification4";
+}
+
+#[tokio::test]
+async fn wait_for_subscriber() {
+    use std::time::Duration;
+
+    println!("wait_for_subscriber: Wait without subscriber");
+    assert!(!wait_subscriber_timeout::<Notification4>(Duration::from_millis(10)).await);
+    let w = tokio::spawn(wait_subscriber::<Notification4>());
+
+    println!("wait_for_subscriber: Subscribe");
+    let mut subscription = subscribe::<Notification4>().await.unwrap();
+    w.await.unwrap();
+    assert!(wait_subscriber_timeout::<Notification4>(Duration::from_millis(10)).await);
+    notify::<Notification4>(()).await.unwrap();
+    subscription.recv().await;
+    subscription.close().await;
+}
+
+struct PriorityNotification;
+
+impl Notification for PriorityNotification {
+    type Payload = i32;
+    const BUFFER_SIZE: usize = 0;
+    const DEBUG_NAME: &'static str = "PriorityNotification";
+}
+
+#[tokio::test]
+async fn priority_notifications() {
+    println!("priority_notifications: Subscribe");
+    let mut subscription = subscribe::<PriorityNotification>().await.unwrap();
+
+    println!("priority_notifications: Notify with priorities");
+    notify::<PriorityNotification>(1).await.unwrap();
+    notify_with_priority::<PriorityNotification>(2, Priority::Low)
+        .await
+        .unwrap();
+    notify_with_priority::<PriorityNotification>(3, Priority::High)
+        .await
+        .unwrap();
+
+    assert_eq!(subscription.recv().await, 3);
+    assert_eq!(subscription.recv().await, 1);
+    assert_eq!(subscription.recv().await, 2);
+    subscription.close().await;
+}
+
+macro_rules! overflow_notification {
+    ($name:ident, $overflow:expr) => {
+        struct $name;
+
+        impl Notification for $name {
+            type Payload = i32;
+            const BUFFER_SIZE: usize = 2;
+            const DEBUG_NAME: &'static str = stringify!($name);
+            const OVERFLOW: Overflow<i32> = $overflow;
+        }
+    };
+}
+
+overflow_notification!(DropNewestNotification, Overflow::DropNewest);
+overflow_notification!(DropOldestNotification, Overflow::DropOldest);
+overflow_notification!(ErrorNotification, Overflow::Error);
+overflow_notification!(BatchSendErrorNotification, Overflow::Error);
+overflow_notification!(
+    CoalesceNotification,
+    Overflow::Coalesce(|last, n| *last += n)
+);
+
+async fn recv_queued<N: Notification>(subscription: &mut Subscription<N>) -> Vec<N::Payload> {
+    let mut payloads = Vec::new();
+    while let Ok(payload) =
+        tokio::time::timeout(std::time::Duration::from_millis(10), subscription.recv()).await
+    {
+        payloads.push(payload);
+    }
+    payloads
+}
+
+#[tokio::test]
+async fn overflow_policies() {
+    println!("overflow_policies: DropNewest");
+    let mut subscription = subscribe::<DropNewestNotification>().await.unwrap();
+    for n in 1..=4 {
+        notify::<DropNewestNotification>(n).await.unwrap();
+    }
+    assert_eq!(recv_queued(&mut subscription).await, [1, 2]);
+    subscription.close().await;
+
+    println!("overflow_policies: DropOldest");
+    let mut subscription = subscribe::<DropOldestNotification>().await.unwrap();
+    for n in 1..=4 {
+        notify::<DropOldestNotification>(n).await.unwrap();
+    }
+    assert_eq!(recv_queued(&mut subscription).await, [3, 4]);
+    subscription.close().await;
+
+    println!("overflow_policies: Error");
+    let mut subscription = subscribe::<ErrorNotification>().await.unwrap();
+    notify::<ErrorNotification>(1).await.unwrap();
+    notify::<ErrorNotification>(2).await.unwrap();
+    assert!(matches!(
+        notify::<ErrorNotification>(3).await,
+        Err(NotifyError::Full(3))
+    ));
+    assert_eq!(recv_queued(&mut subscription).await, [1, 2]);
+    subscription.close().await;
+
+    println!("overflow_policies: Coalesce");
+    let mut subscription = subscribe::<CoalesceNotification>().await.unwrap();
+    for n in 1..=4 {
+        notify::<CoalesceNotification>(n).await.unwrap();
+    }
+    assert_eq!(recv_queued(&mut subscription).await, [1, 9]);
+    subscription.close().await;
+}
+
+struct DedupNotification;
+
+impl Notification for DedupNotification {
+    type Payload = (u64, i32);
+    const BUFFER_SIZE: usize = 2;
+    const DEBUG_NAME: &'static str = "DedupNotification";
+    const DEDUP: Option<fn(&(u64, i32)) -> u64> = Some(|(key, _)| *key);
+}
+
+struct RefreshNotification;
+
+impl Notification for RefreshNotification {
+    type Payload = ();
+    const BUFFER_SIZE: usize = 0;
+    const DEBUG_NAME: &'static str = "RefreshNotification";
+    const DEDUP: Option<fn(&()) -> u64> = Some(|_| 0);
+}
+
+#[tokio::test]
+async fn dedup_notifications() {
+    println!("dedup_notifications: Dedup by key");
+    let mut subscription = subscribe::<DedupNotification>().await.unwrap();
+    for payload in [(1, 10), (2, 20), (1, 11), (2, 21), (1, 12)] {
+        notify::<DedupNotification>(payload).await.unwrap();
+    }
+    assert_eq!(recv_queued(&mut subscription).await, [(1, 12), (2, 21)]);
+    subscription.close().await;
+
+    println!("dedup_notifications: Collapse unit notification");
+    let mut subscription = subscribe::<RefreshNotification>().await.unwrap();
+    for _ in 0..10 {
+        notify::<RefreshNotification>(()).await.unwrap();
+    }
+    assert_eq!(recv_queued(&mut subscription).await, [()]);
+    notify::<RefreshNotification>(()).await.unwrap();
+    assert_eq!(recv_queued(&mut subscription).await, [()]);
+    subscription.close().await;
+}
+
+struct ExpiringNotification;
+
+impl Notification for ExpiringNotification {
+    type Payload = i32;
+    const BUFFER_SIZE: usize = 0;
+    const DEBUG_NAME: &'static str = "ExpiringNotification";
+    const TTL: Option<Duration> = Some(Duration::from_millis(50));
+}
+
+#[tokio::test]
+async fn expired_notifications() {
+    tokio::time::pause();
+    println!("expired_notifications: Queue notifications before receiving");
+    let mut subscription = subscribe::<ExpiringNotification>().await.unwrap();
+    notify::<ExpiringNotification>(1).await.unwrap();
+    notify_with_ttl::<ExpiringNotification>(2, Duration::from_millis(500))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    notify::<ExpiringNotification>(3).await.unwrap();
+
+    println!("expired_notifications: Expired notification is skipped");
+    assert_eq!(recv_queued(&mut subscription).await, [2, 3]);
+    assert_eq!(expired_count::<ExpiringNotification>(), 1);
+    subscription.close().await;
+}
+
+struct BatchedNotification;
+
+impl Notification for BatchedNotification {
+    type Payload = i32;
+    const BUFFER_SIZE: usize = 0;
+    const DEBUG_NAME: &'static str = "BatchedNotification";
+}
+
+#[tokio::test]
+async fn batch_receive() {
+    println!("batch_receive: Queue notifications");
+    let mut subscription = subscribe::<BatchedNotification>().await.unwrap();
+    for n in 1..=5 {
+        notify::<BatchedNotification>(n).await.unwrap();
+    }
+
+    println!("batch_receive: Receive batches");
+    let mut buffer = Vec::new();
+    assert_eq!(subscription.recv_many(&mut buffer, 3).await, 3);
+    assert_eq!(subscription.recv_many(&mut buffer, 3).await, 2);
+    assert_eq!(subscription.recv_many(&mut buffer, 0).await, 0);
+    assert_eq!(buffer, [1, 2, 3, 4, 5]);
+
+    println!("batch_receive: Wait for the first notification");
+    let notifier = tokio::spawn(async {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        notify::<BatchedNotification>(6).await.unwrap();
+    });
+    buffer.clear();
+    assert_eq!(subscription.recv_many(&mut buffer, 3).await, 1);
+    assert_eq!(buffer, [6]);
+    notifier.await.unwrap();
+    subscription.close().await;
+}
+
+struct BatchSendNotification;
+
+impl Notification for BatchSendNotification {
+    type Payload = i32;
+    const BUFFER_SIZE: usize = 3;
+    const DEBUG_NAME: &'static str = "BatchSendNotification";
+}
+
+#[tokio::test]
+async fn batch_send() {
+    println!("batch_send: Not subscribed");
+    let error = notify_all::<BatchSendNotification, _>([1, 2, 3])
+        .await
+        .unwrap_err();
+    assert_eq!(error.accepted, 0);
+    assert!(matches!(error.error, NotifyError::NotSubscribed(1)));
+    assert_eq!(error.rest, [2, 3]);
+
+    println!("batch_send: Send in bulk to the bounded subscription");
+    let mut subscription = subscribe::<BatchSendNotification>().await.unwrap();
+    let sender = tokio::spawn(notify_all::<BatchSendNotification, _>(1..=10));
+    tokio::task::yield_now().await;
+
+    println!("batch_send: Subscribe while the sender waits");
+    let other = tokio::time::timeout(
+        Duration::from_secs(1),
+        subscribe::<BatchSendErrorNotification>(),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    other.close().await;
+    let mut buffer = Vec::new();
+    while buffer.len() < 10 {
+        subscription.recv_many(&mut buffer, 10).await;
+    }
+    assert_eq!(sender.await.unwrap().unwrap(), 10);
+    assert_eq!(buffer, (1..=10).collect::<Vec<_>>());
+    subscription.close().await;
+
+    println!("batch_send: Stop on the full subscription");
+    let mut subscription = subscribe::<BatchSendErrorNotification>().await.unwrap();
+    let error = notify_all::<BatchSendErrorNotification, _>([1, 2, 3, 4])
+        .await
+        .unwrap_err();
+    assert_eq!(error.accepted, 2);
+    assert!(matches!(error.error, NotifyError::Full(3)));
+    assert_eq!(error.rest, [4]);
+    assert_eq!(recv_queued(&mut subscription).await, [1, 2]);
+    subscription.close().await;
+}
@@ -0,0 +1,31 @@
+//! Priorities
+//!
+//! Priority lanes of notifications and requests
+
+/// Priority of the notification or the request
+///
+/// Higher priority notifications and requests are received first,
+/// see [notify_with_priority](crate::notification::notify_with_priority)
+/// and [request_with_priority](crate::request::request_with_priority)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Priority {
+    /// Bulk traffic
+    Low,
+    /// Priority of [notify](crate::notification::notify)
+    /// and [request](crate::request::request)
+    #[default]
+    Normal,
+    /// Urgent control messages
+    High,
+}
+
+impl Priority {
+    /// Index of the queue lane, the highest priority lane is the first
+    pub(crate) fn lane(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Rate limiting
+//!
+//! Debounce, throttle and sample adaptors of the subscriptions
+
+use crate::{broadcast, notification};
+use std::{future::Future, pin::Pin, time::Duration};
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+#[cfg(test)]
+mod test;
+
+/// Future of the [Receive::recv]
+pub type RecvFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Receiver of the items adapted by [Debounce], [Throttle] and [Sample]
+///
+/// Implemented by the notification and broadcast subscriptions
+/// and by the adaptors themselves, so they can be chained
+pub trait Receive: Send {
+    /// Received item
+    type Item: Send;
+
+    /// Receives the next item
+    fn recv(&mut self) -> RecvFuture<'_, Self::Item>;
+}
+
+impl<N: notification::Notification> Receive for notification::Subscription<N> {
+    type Item = N::Payload;
+
+    fn recv(&mut self) -> RecvFuture<'_, Self::Item> {
+        Box::pin(notification::Subscription::recv(self))
+    }
+}
+
+impl<B: broadcast::Broadcast> Receive for broadcast::Subscription<B> {
+    type Item = B::Payload;
+
+    fn recv(&mut self) -> RecvFuture<'_, Self::Item> {
+        Box::pin(broadcast::Subscription::recv(self))
+    }
+}
+
+impl<B: broadcast::Broadcast> Receive for broadcast::TopicSubscription<B> {
+    type Item = (String, B::Payload);
+
+    fn recv(&mut self) -> RecvFuture<'_, Self::Item> {
+        Box::pin(broadcast::TopicSubscription::recv(self))
+    }
+}
+
+/// Adaptor receiving an item only after
+/// no newer items are received for the quiet period
+///
+/// The newer items received in the quiet period replace the pending one
+pub struct Debounce<S: Receive> {
+    inner: S,
+    period: Duration,
+    /// Pending item and its deadline
+    pending: Option<(S::Item, Instant)>,
+}
+
+impl<S: Receive> Debounce<S> {
+    /// Creates the adaptor with the quiet `period`
+    pub fn new(inner: S, period: Duration) -> Self {
+        Self {
+            inner,
+            period,
+            pending: None,
+        }
+    }
+
+    /// Receives the latest item after the quiet period
+    ///
+    /// The pending item is kept if the future is cancelled
+    pub async fn recv(&mut self) -> S::Item {
+        loop {
+            let deadline = match &self.pending {
+                Some((_, deadline)) => *deadline,
+                None => {
+                    let item = self.inner.recv().await;
+                    self.pending = Some((item, Instant::now() + self.period));
+                    continue;
+                }
+            };
+            tokio::select! {
+                item = self.inner.recv() => {
+                    self.pending = Some((item, Instant::now() + self.period));
+                }
+                _ = tokio::time::sleep_until(deadline) => {
+                    if let Some((item, _)) = self.pending.take() {
+                        return item;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the adapted receiver, the pending item is dropped
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+/// Adaptor receiving at most one item per period
+///
+/// The first item is received immediately,
+/// the items received in the period after it are dropped
+pub struct Throttle<S: Receive> {
+    inner: S,
+    period: Duration,
+    /// End of the current period
+    until: Option<Instant>,
+}
+
+impl<S: Receive> Throttle<S> {
+    /// Creates the adaptor with the `period`
+    pub fn new(inner: S, period: Duration) -> Self {
+        Self {
+            inner,
+            period,
+            until: None,
+        }
+    }
+
+    /// Receives the first item after the current period
+    pub async fn recv(&mut self) -> S::Item {
+        loop {
+            let item = self.inner.recv().await;
+            let now = Instant::now();
+            if self.until.map_or(true, |until| now >= until) {
+                self.until = Some(now + self.period);
+                return item;
+            }
+        }
+    }
+
+    /// Returns the adapted receiver
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+/// Adaptor receiving the latest item once per period
+///
+/// The periods without items are skipped
+pub struct Sample<S: Receive> {
+    inner: S,
+    interval: Interval,
+    latest: Option<S::Item>,
+}
+
+impl<S: Receive> Sample<S> {
+    /// Creates the adaptor with the `period`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero
+    pub fn new(inner: S, period: Duration) -> Self {
+        let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Self {
+            inner,
+            interval,
+            latest: None,
+        }
+    }
+
+    /// Receives the latest item at the end of the period
+    ///
+    /// The latest item is kept if the future is cancelled
+    pub async fn recv(&mut self) -> S::Item {
+        loop {
+            tokio::select! {
+                item = self.inner.recv() => self.latest = Some(item),
+                _ = self.interval.tick() => {
+                    if let Some(item) = self.latest.take() {
+                        return item;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the adapted receiver, the latest item is dropped
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Receive> Receive for Debounce<S> {
+    type Item = S::Item;
+
+    fn recv(&mut self) -> RecvFuture<'_, Self::Item> {
+        Box::pin(Debounce::recv(self))
+    }
+}
+
+impl<S: Receive> Receive for Throttle<S> {
+    type Item = S::Item;
+
+    fn recv(&mut self) -> RecvFuture<'_, Self::Item> {
+        Box::pin(Throttle::recv(self))
+    }
+}
+
+impl<S: Receive> Receive for Sample<S> {
+    type Item = S::Item;
+
+    fn recv(&mut self) -> RecvFuture<'_, Self::Item> {
+        Box::pin(Sample::recv(self))
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{broadcast, notification};
+use std::time::Duration;
+
+crate::declare! {
+    notification Changed(i32);
+    broadcast[16] Ticks(i32);
+}
+
+async fn produce<F, Fut>(notify: F, delays: &'static [(i32, u64)])
+where
+    F: Fn(i32) -> Fut,
+    Fut: std::future::Future,
+{
+    for &(payload, delay) in delays {
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+        notify(payload).await;
+    }
+}
+
+async fn recv_all<S: super::Receive>(mut receiver: S) -> Vec<S::Item> {
+    let mut items = Vec::new();
+    while let Ok(item) = tokio::time::timeout(Duration::from_millis(200), receiver.recv()).await {
+        items.push(item);
+    }
+    items
+}
+
+#[tokio::test]
+async fn debounce() {
+    tokio::time::pause();
+    println!("debounce: Subscribe");
+    let subscription = notification::subscribe::<Changed>().await.unwrap();
+    let producer = tokio::spawn(produce(
+        |n| async move { Changed::notify(n).await.unwrap() },
+        &[(1, 0), (2, 10), (3, 10), (4, 100)],
+    ));
+    let items = recv_all(subscription.debounce(Duration::from_millis(50))).await;
+    producer.await.unwrap();
+    assert_eq!(items, [3, 4]);
+
+    println!("debounce: Subscribe topic");
+    let subscription = broadcast::subscribe_topic::<Ticks>("ticks.*")
+        .await
+        .unwrap();
+    let producer = tokio::spawn(produce(
+        |n| Ticks::notify_topic("ticks.a", n),
+        &[(1, 0), (2, 10)],
+    ));
+    let items = recv_all(subscription.debounce(Duration::from_millis(50))).await;
+    producer.await.unwrap();
+    assert_eq!(items, [("ticks.a".to_string(), 2)]);
+}
+
+#[tokio::test]
+async fn throttle_and_sample() {
+    tokio::time::pause();
+    let delays = &[(0, 0), (1, 20), (2, 20), (3, 20), (4, 20), (5, 20)];
+
+    println!("throttle_and_sample: Throttle");
+    let subscription = broadcast::subscribe::<Ticks>().await;
+    let producer = tokio::spawn(produce(Ticks::notify, delays));
+    let items = recv_all(subscription.throttle(Duration::from_millis(50))).await;
+    producer.await.unwrap();
+    assert_eq!(items, [0, 3]);
+
+    println!("throttle_and_sample: Sample");
+    let subscription = broadcast::subscribe::<Ticks>().await;
+    let producer = tokio::spawn(produce(Ticks::notify, &delays[..5]));
+    let items = recv_all(subscription.sample(Duration::from_millis(50))).await;
+    producer.await.unwrap();
+    assert_eq!(items, [2, 4]);
+}
@@ -0,0 +1,262 @@
+use super::{deliver, ReplyFuture, Request};
+use crate::{
+    common::{StaticTypeMap, UntypedBox},
+    priority::Priority,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::time::Instant;
+
+static CACHES: StaticTypeMap = StaticTypeMap::new();
+
+/// Caching of the request responses used by [Request::CACHE]
+///
+/// The successful responses are kept for `ttl`
+/// in the LRU cache of up to `capacity` payloads
+pub struct Cache<R: Request> {
+    ttl: Duration,
+    capacity: usize,
+    request: fn(R::Payload, Priority) -> ReplyFuture<R>,
+}
+
+/// Counters of the request cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of the requests responded from the cache
+    pub hits: u64,
+    /// The number of the requests delivered to the listener
+    pub misses: u64,
+    /// The number of the cached responses
+    pub len: usize,
+}
+
+impl<R: Request> Cache<R> {
+    /// Creates the cache, `request` is [cached]
+    pub const fn new(
+        ttl: Duration,
+        capacity: usize,
+        request: fn(R::Payload, Priority) -> ReplyFuture<R>,
+    ) -> Self {
+        Self {
+            ttl,
+            capacity,
+            request,
+        }
+    }
+
+    /// Returns the time to live of the cached responses
+    pub const fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Returns the maximum number of the cached responses
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub(super) fn request(&self, payload: R::Payload, priority: Priority) -> ReplyFuture<R> {
+        (self.request)(payload, priority)
+    }
+}
+
+impl<R: Request> Clone for Cache<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R: Request> Copy for Cache<R> {}
+
+/// Sends a payload to the [Listener](super::Listener)
+/// unless its response is cached
+///
+/// It is intended to be set as [Request::CACHE],
+/// so the [request](super::request) uses it
+pub fn cached<R>(payload: R::Payload, priority: Priority) -> ReplyFuture<R>
+where
+    R: Request,
+    R::Payload: Hash + Eq + Clone,
+    R::Response: Clone,
+{
+    Box::pin(async move {
+        let cache = match R::CACHE {
+            Some(cache) => cache,
+            None => return deliver::<R>(payload, priority).await,
+        };
+        let store = store::<R>().await;
+        if let Some(response) = store.lock().get(&payload) {
+            return Ok(response);
+        }
+        let key = payload.clone();
+        let reply = deliver::<R>(payload, priority).await;
+        if let Ok(response) = &reply {
+            store.lock().insert(key, response.clone(), &cache);
+        }
+        reply
+    })
+}
+
+/// Removes the cached response for the payload
+///
+/// Returns false if the response is not cached
+pub async fn invalidate<R>(payload: &R::Payload) -> bool
+where
+    R: Request,
+    R::Payload: Hash + Eq + Clone,
+    R::Response: Clone,
+{
+    store::<R>().await.lock().remove(payload)
+}
+
+/// Removes all the cached responses of the request
+pub async fn invalidate_all<R>()
+where
+    R: Request,
+    R::Payload: Hash + Eq + Clone,
+    R::Response: Clone,
+{
+    store::<R>().await.lock().clear()
+}
+
+/// Returns the counters of the request cache
+pub async fn cache_stats<R>() -> CacheStats
+where
+    R: Request,
+    R::Payload: Hash + Eq + Clone,
+    R::Response: Clone,
+{
+    store::<R>().await.lock().stats()
+}
+
+async fn store<R>() -> Arc<Mutex<Store<R>>>
+where
+    R: Request,
+    R::Payload: Hash + Eq + Clone,
+    R::Response: Clone,
+{
+    let id = id!(R);
+    if let Some(store) = CACHES.read().await.get(&id) {
+        let store: &Arc<Mutex<Store<R>>> = unsafe { store.get_ref() };
+        return store.clone();
+    }
+    let mut caches = CACHES.write().await;
+    let store = caches
+        .entry(id)
+        .or_insert_with(|| UntypedBox::new(Arc::new(Mutex::new(Store::<R>::new()))));
+    let store: &Arc<Mutex<Store<R>>> = unsafe { store.get_ref() };
+    store.clone()
+}
+
+/// LRU storage of the cached responses
+struct Store<R: Request> {
+    entries: HashMap<R::Payload, Entry<R>>,
+    /// Payloads by their last use
+    used: BTreeMap<u64, R::Payload>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+struct Entry<R: Request> {
+    response: R::Response,
+    expires: Instant,
+    used: u64,
+}
+
+impl<R> Store<R>
+where
+    R: Request,
+    R::Payload: Hash + Eq + Clone,
+    R::Response: Clone,
+{
+    fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            used: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn get(&mut self, payload: &R::Payload) -> Option<R::Response> {
+        let now = Instant::now();
+        let tick = self.next_tick();
+        let entry = match self.entries.get_mut(payload) {
+            Some(entry) if entry.expires > now => entry,
+            Some(_) => {
+                self.remove(payload);
+                self.misses += 1;
+                return None;
+            }
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        };
+        if let Some(payload) = self.used.remove(&entry.used) {
+            self.used.insert(tick, payload);
+        }
+        entry.used = tick;
+        self.hits += 1;
+        Some(entry.response.clone())
+    }
+
+    fn insert(&mut self, payload: R::Payload, response: R::Response, cache: &Cache<R>) {
+        if cache.capacity == 0 {
+            return;
+        }
+        self.remove(&payload);
+        while self.entries.len() >= cache.capacity {
+            // evict the least recently used response
+            let oldest = match self.used.keys().next() {
+                Some(&oldest) => oldest,
+                None => break,
+            };
+            if let Some(payload) = self.used.remove(&oldest) {
+                self.entries.remove(&payload);
+            }
+        }
+        let used = self.next_tick();
+        self.used.insert(used, payload.clone());
+        let entry = Entry {
+            response,
+            expires: Instant::now() + cache.ttl,
+            used,
+        };
+        self.entries.insert(payload, entry);
+    }
+
+    fn remove(&mut self, payload: &R::Payload) -> bool {
+        match self.entries.remove(payload) {
+            Some(entry) => {
+                self.used.remove(&entry.used);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.used.clear();
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            len: self.entries.len(),
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
@@ -0,0 +1,198 @@
+use super::{Reply, Request, RequestError};
+use crate::common::OnceCell;
+use parking_lot::Mutex;
+use std::{any::TypeId, collections::HashMap, future::Future, time::Duration};
+use tokio::time::Instant;
+
+static CIRCUITS: OnceCell<Mutex<HashMap<TypeId, Circuit>>> = OnceCell::new();
+
+/// Circuit breaker of the request used by [Request::CIRCUIT_BREAKER]
+///
+/// The circuit opens after `failure_threshold` consecutive failures
+/// and the requests fail fast with [RequestError::CircuitOpen].
+/// After `reset_timeout` the circuit half-opens
+/// and lets one probe request to the listener,
+/// its success closes the circuit and its failure opens it again
+///
+/// The failures are [RequestError::Handler], [RequestError::HandlerPanicked],
+/// [RequestError::NotResponded] and [RequestError::TimedOut]
+///
+/// ## Example
+///
+/// ```rust
+/// use intercomm::request::CircuitBreaker;
+/// use std::time::Duration;
+///
+/// const BREAKER: CircuitBreaker = CircuitBreaker::new(5, Duration::from_secs(10))
+///     .with_timeout(Duration::from_secs(1));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    timeout: Option<Duration>,
+}
+
+/// State of the request circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// The requests are sent to the listener
+    Closed,
+    /// The requests fail fast
+    Open,
+    /// One probe request is sent to the listener, the others fail fast
+    HalfOpen,
+}
+
+impl CircuitBreaker {
+    /// Creates the circuit breaker
+    ///
+    /// `failure_threshold` of 0 is treated as 1
+    pub const fn new(failure_threshold: u32, reset_timeout: Duration) -> Self {
+        Self {
+            failure_threshold,
+            reset_timeout,
+            timeout: None,
+        }
+    }
+
+    /// Sets the time of waiting for the response,
+    /// the slower responses are failed with [RequestError::TimedOut]
+    pub const fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+}
+
+/// Returns the state of the request circuit
+///
+/// It is always [CircuitState::Closed]
+/// if [Request::CIRCUIT_BREAKER] is not set
+pub fn circuit_state<R: Request>() -> CircuitState {
+    match R::CIRCUIT_BREAKER {
+        Some(breaker) => circuits()
+            .lock()
+            .get(&id!(R))
+            .map_or(CircuitState::Closed, |circuit| circuit.state(&breaker)),
+        None => CircuitState::Closed,
+    }
+}
+
+/// Closes the request circuit
+pub fn reset_circuit<R: Request>() {
+    circuits().lock().remove(&id!(R));
+}
+
+/// Sends the payload by `send` through the circuit breaker
+/// if [Request::CIRCUIT_BREAKER] is set
+pub(super) async fn guard<R, F, Fut>(payload: R::Payload, send: F) -> Reply<R>
+where
+    R: Request,
+    F: FnOnce(R::Payload) -> Fut,
+    Fut: Future<Output = Reply<R>>,
+{
+    let breaker = match R::CIRCUIT_BREAKER {
+        Some(breaker) => breaker,
+        None => return send(payload).await,
+    };
+    let permit = match Permit::<R>::acquire(&breaker) {
+        Some(permit) => permit,
+        None => return Err(RequestError::CircuitOpen(payload)),
+    };
+    let reply = match breaker.timeout {
+        Some(timeout) => tokio::time::timeout(timeout, send(payload))
+            .await
+            .unwrap_or(Err(RequestError::TimedOut)),
+        None => send(payload).await,
+    };
+    let failed = matches!(
+        reply,
+        Err(RequestError::Handler(_))
+            | Err(RequestError::HandlerPanicked { .. })
+            | Err(RequestError::NotResponded)
+            | Err(RequestError::TimedOut)
+    );
+    permit.complete(&breaker, failed);
+    reply
+}
+
+fn circuits() -> &'static Mutex<HashMap<TypeId, Circuit>> {
+    CIRCUITS.get_or_init(Default::default)
+}
+
+#[derive(Default)]
+struct Circuit {
+    failures: u32,
+    opened_at: Option<Instant>,
+    probing: bool,
+}
+
+impl Circuit {
+    fn state(&self, breaker: &CircuitBreaker) -> CircuitState {
+        match self.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() < breaker.reset_timeout => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+}
+
+/// Permission to send the request through the circuit
+struct Permit<R: Request> {
+    probe: bool,
+    completed: bool,
+    _request: std::marker::PhantomData<fn() -> R>,
+}
+
+impl<R: Request> Permit<R> {
+    fn acquire(breaker: &CircuitBreaker) -> Option<Self> {
+        let mut circuits = circuits().lock();
+        let circuit = circuits.entry(id!(R)).or_default();
+        let probe = match circuit.state(breaker) {
+            CircuitState::Closed => false,
+            CircuitState::Open => return None,
+            CircuitState::HalfOpen if circuit.probing => return None,
+            CircuitState::HalfOpen => {
+                circuit.probing = true;
+                true
+            }
+        };
+        Some(Self {
+            probe,
+            completed: false,
+            _request: std::marker::PhantomData,
+        })
+    }
+
+    fn complete(mut self, breaker: &CircuitBreaker, failed: bool) {
+        self.completed = true;
+        let mut circuits = circuits().lock();
+        let circuit = circuits.entry(id!(R)).or_default();
+        if self.probe {
+            circuit.probing = false;
+        }
+        if !failed {
+            circuit.failures = 0;
+            circuit.opened_at = None;
+            return;
+        }
+        circuit.failures += 1;
+        if self.probe || circuit.failures >= breaker.failure_threshold {
+            circuit.failures = 0;
+            circuit.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+impl<R: Request> Drop for Permit<R> {
+    fn drop(&mut self) {
+        // cancelled probe lets another request to probe
+        if self.probe && !self.completed {
+            if let Some(circuit) = circuits().lock().get_mut(&id!(R)) {
+                circuit.probing = false;
+            }
+        }
+    }
+}
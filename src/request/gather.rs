@@ -0,0 +1,181 @@
+use super::{
+    receive_reply, Listeners, Reply, ReplyFuture, Request, RequestError, RequestPair, Ttl, CHANNELS,
+};
+use crate::priority::Priority;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{sync::oneshot, time::Instant};
+
+/// Sends a copy of the payload to every [Listener](crate::request::Listener)
+/// of the request and waits for all of the responses
+///
+/// Listeners that have not accepted the request or responded in `timeout`
+/// are reported as [RequestError::TimedOut],
+/// the requests not accepted in [Request::TTL] as [RequestError::Expired]
+///
+/// Returns the only [RequestError::NotListened] if there are no listeners
+pub async fn gather<R>(
+    payload: R::Payload,
+    timeout: Duration,
+) -> Vec<Result<R::Response, RequestError<R>>>
+where
+    R: Request,
+    R::Payload: Clone,
+{
+    let deadline = Instant::now() + timeout;
+    let receivers = match scatter::<R>(&payload, deadline, 1).await {
+        Some(receivers) => receivers,
+        None => return vec![Err(RequestError::NotListened(payload))],
+    };
+    let mut replies = Vec::with_capacity(receivers.len());
+    for rx in receivers {
+        let rx = match rx {
+            Ok(rx) => rx,
+            Err(e) => {
+                replies.push(Err(e));
+                continue;
+            }
+        };
+        let reply = match tokio::time::timeout_at(deadline, rx).await {
+            Ok(reply) => reply,
+            Err(_) => Err(RequestError::TimedOut),
+        };
+        replies.push(reply);
+    }
+    replies
+}
+
+/// Sends a copy of the payload to every [Listener](crate::request::Listener)
+/// of the request and waits for the first successful response
+///
+/// Returns the last error if all of the listeners have failed
+/// or [RequestError::TimedOut] if none has responded in `timeout`
+pub async fn first_of<R>(
+    payload: R::Payload,
+    timeout: Duration,
+) -> Result<R::Response, RequestError<R>>
+where
+    R: Request,
+    R::Payload: Clone,
+{
+    quorum::<R>(payload, 1, timeout)
+        .await
+        .map(|mut responses| responses.remove(0))
+}
+
+/// Sends a copy of the payload to every [Listener](crate::request::Listener)
+/// of the request and waits for `n` successful responses
+///
+/// Returns [RequestError::NotListened] if there are less than `n` listeners,
+/// the last error if too many of the listeners have failed
+/// or [RequestError::TimedOut] if `n` responses are not received in `timeout`
+pub async fn quorum<R>(
+    payload: R::Payload,
+    n: usize,
+    timeout: Duration,
+) -> Result<Vec<R::Response>, RequestError<R>>
+where
+    R: Request,
+    R::Payload: Clone,
+{
+    let deadline = Instant::now() + timeout;
+    let scattered = match scatter::<R>(&payload, deadline, n).await {
+        Some(scattered) => scattered,
+        None => return Err(RequestError::NotListened(payload)),
+    };
+    let count = scattered.len();
+    let mut receivers: Vec<_> = scattered.into_iter().filter_map(Result::ok).collect();
+    if receivers.len() < n {
+        // the deadline is reached while sending
+        if receivers.len() < count {
+            return Err(RequestError::TimedOut);
+        }
+        return Err(RequestError::NotListened(payload));
+    }
+    let mut responses = Vec::with_capacity(n);
+    while responses.len() < n {
+        let reply = match tokio::time::timeout_at(deadline, NextReply(&mut receivers)).await {
+            Ok(reply) => reply,
+            Err(_) => return Err(RequestError::TimedOut),
+        };
+        match reply {
+            Ok(response) => responses.push(response),
+            Err(e) if responses.len() + receivers.len() < n => return Err(e),
+            Err(_) => {}
+        }
+    }
+    Ok(responses)
+}
+
+/// Future of the reply or the error of sending the request
+type Scattered<R> = Result<ReplyFuture<R>, RequestError<R>>;
+
+/// Sends a copy of the payload to every open listener
+///
+/// The listeners not accepting the request before the `deadline`
+/// are reported as [RequestError::TimedOut]
+///
+/// Returns None if there are less than `min` open listeners
+/// or the request is not sent to any of them
+async fn scatter<R>(
+    payload: &R::Payload,
+    deadline: Instant,
+    min: usize,
+) -> Option<Vec<Scattered<R>>>
+where
+    R: Request,
+    R::Payload: Clone,
+{
+    let channels = CHANNELS.read().await;
+    let listeners: &Listeners<R> = unsafe { channels.get(&id!(R))?.get_ref() };
+    if listeners.open().count() < min {
+        return None;
+    }
+    let mut receivers = Vec::new();
+    for listener in listeners.open() {
+        let (tx, rx) = oneshot::channel();
+        let ttl = Ttl::new(R::TTL);
+        let request_pair = RequestPair::<R> {
+            payload: payload.clone(),
+            responder: tx,
+            priority: Priority::Normal,
+            ttl: ttl.clone(),
+        };
+        match tokio::time::timeout_at(deadline, listener.send(request_pair)).await {
+            Ok(Ok(())) => {
+                let reply: ReplyFuture<R> = Box::pin(receive_reply(rx, ttl));
+                receivers.push(Ok(reply));
+            }
+            // closed listener is skipped
+            Ok(Err(_)) => {}
+            Err(_) => receivers.push(Err(RequestError::TimedOut)),
+        }
+    }
+    if receivers.is_empty() {
+        return None;
+    }
+    Some(receivers)
+}
+
+/// Future that resolves with the first received reply
+/// and removes its future
+struct NextReply<'a, R: Request>(&'a mut Vec<ReplyFuture<R>>);
+
+impl<R: Request> Future for NextReply<'_, R> {
+    type Output = Reply<R>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let receivers = &mut *self.0;
+        for i in 0..receivers.len() {
+            if let Poll::Ready(reply) = receivers[i].as_mut().poll(cx) {
+                drop(receivers.swap_remove(i));
+                return Poll::Ready(reply);
+            }
+        }
+        Poll::Pending
+    }
+}
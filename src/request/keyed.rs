@@ -0,0 +1,128 @@
+use super::{
+    intercept, registry::ListenerEntry, send_by, Listener, Request, RequestError, RequestPair,
+    KEYED_CHANNELS,
+};
+use crate::{common::UntypedBox, layer::Stage, priority::Priority};
+use std::{any::TypeId, collections::HashMap, hash::Hash};
+
+type KeyedListeners<R, K> = HashMap<K, ListenerEntry<R>>;
+
+/// Listen to request with the key
+///
+/// Each key has its own [Listener] with its own queue,
+/// requests are sent to it with [request_keyed]
+///
+/// Returns None if request is already listened with the key
+pub async fn listen_keyed<R, K>(key: K) -> Option<Listener<R>>
+where
+    R: Request,
+    K: Hash + Eq + Send + Sync + 'static,
+{
+    let mut channels = KEYED_CHANNELS.write().await;
+    let listeners = channels
+        .entry(id!((R, K)))
+        .or_insert_with(|| UntypedBox::new(KeyedListeners::<R, K>::new()));
+    let listeners: &mut KeyedListeners<R, K> = unsafe { listeners.get_mut() };
+    if listeners.contains_key(&key) {
+        return None;
+    }
+    let (listener, entry) = Listener::new(&KEYED_CHANNELS, remove_keyed_listener::<R, K>, false);
+    listeners.insert(key, entry);
+    Some(listener)
+}
+
+/// Sends a payload to the [Listener] of the key
+///
+/// Uses the same options as [request](super::request)
+/// except [Request::SINGLE_FLIGHT] and [Request::CACHE]
+///
+/// See [listen_keyed]
+pub async fn request_keyed<R, K>(
+    key: &K,
+    payload: R::Payload,
+) -> Result<R::Response, RequestError<R>>
+where
+    R: Request,
+    K: Hash + Eq + Send + Sync + 'static,
+{
+    request_keyed_with_priority::<R, K>(key, payload, Priority::Normal).await
+}
+
+/// Sends a payload with the priority to the [Listener] of the key
+///
+/// Higher priority requests are accepted first
+///
+/// Uses the same options as [request_keyed]
+pub async fn request_keyed_with_priority<R, K>(
+    key: &K,
+    payload: R::Payload,
+    priority: Priority,
+) -> Result<R::Response, RequestError<R>>
+where
+    R: Request,
+    K: Hash + Eq + Send + Sync + 'static,
+{
+    let mut payload = payload;
+    intercept::<R>(Stage::Send, &mut payload)?;
+    send_by::<R, _, _>(payload, priority, |request_pair| {
+        dispatch_keyed::<R, K>(key, request_pair)
+    })
+    .await
+}
+
+async fn dispatch_keyed<R, K>(key: &K, request_pair: RequestPair<R>) -> Result<(), RequestError<R>>
+where
+    R: Request,
+    K: Hash + Eq + Send + Sync + 'static,
+{
+    let listener = {
+        let channels = KEYED_CHANNELS.read().await;
+        match channels.get(&id!((R, K))) {
+            Some(listeners) => unsafe { listeners.get_ref::<KeyedListeners<R, K>>() }
+                .get(key)
+                .cloned(),
+            None => None,
+        }
+    };
+    // the registry is not locked while the full queue is awaited
+    match listener {
+        Some(listener) => listener.send(request_pair).await.map_err(Into::into),
+        None => Err(RequestError::NotListened(request_pair.payload)),
+    }
+}
+
+/// Returns the keys the request is listened with
+///
+/// See [listen_keyed]
+pub async fn listened_keys<R, K>() -> Vec<K>
+where
+    R: Request,
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    let channels = KEYED_CHANNELS.read().await;
+    let listeners = match channels.get(&id!((R, K))) {
+        Some(listeners) => unsafe { listeners.get_ref::<KeyedListeners<R, K>>() },
+        None => return Vec::new(),
+    };
+    listeners
+        .iter()
+        .filter(|(_, listener)| !listener.is_closed())
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+fn remove_keyed_listener<R, K>(channels: &mut HashMap<TypeId, UntypedBox>, id: usize) -> bool
+where
+    R: Request,
+    K: Hash + Eq + Send + Sync + 'static,
+{
+    let type_id = id!((R, K));
+    if let Some(listeners) = channels.get_mut(&type_id) {
+        let listeners: &mut KeyedListeners<R, K> = unsafe { listeners.get_mut() };
+        listeners.retain(|_, listener| listener.id != id);
+        if listeners.is_empty() {
+            channels.remove(&type_id);
+        }
+    }
+    false
+}
@@ -1,11 +1,40 @@
-use super::{Request, RequestPair, CHANNELS};
-use crate::common::UntypedBox;
-use std::mem;
-use tokio::sync::mpsc::{channel, unbounded_channel, Receiver, UnboundedReceiver};
+use super::{
+    dispatch, intercept,
+    registry::{ListenerEntry, Listeners},
+    Request, RequestError, RequestPair, Responder, Strategy, CHANNELS, EXPIRED,
+};
+use crate::{
+    common::{catch_unwind, CatchUnwind, Queue, StaticTypeMap, UntypedBox},
+    layer::Stage,
+};
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::{sync::Semaphore, task::JoinHandle};
+
+static LISTENER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Removes the listener with the id from the registry,
+/// returns true if its queued requests should be routed to other listeners
+pub(super) type Unregister = fn(&mut HashMap<TypeId, UntypedBox>, usize) -> bool;
 
 /// Request listener
 pub struct Listener<R: Request> {
-    receiver: RequestReceiver<R>,
+    id: usize,
+    /// None if the listener is closed
+    queue: Option<Arc<Queue<RequestPair<R>>>>,
+    queued: Arc<AtomicUsize>,
+    catch_panics: bool,
+    /// Whether the queued requests are routed to the other listeners on drop
+    shared: bool,
+    registry: &'static StaticTypeMap,
+    unregister: Unregister,
 }
 
 /// Listen to request
@@ -17,70 +46,470 @@ pub async fn listen<R: Request>() -> Option<Listener<R>> {
     if channels.contains_key(&id) {
         return None;
     }
-    let (sender, receiver) = if R::BUFFER_SIZE == 0 {
-        let (tx, rx) = unbounded_channel();
-        let tx = UntypedBox::new(tx);
-        let rx = RequestReceiver::Unbounded(rx);
-        (tx, rx)
-    } else {
-        let (tx, rx) = channel(R::BUFFER_SIZE);
-        let tx = UntypedBox::new(tx);
-        let rx = RequestReceiver::Bounded(rx);
-        (tx, rx)
-    };
-    channels.insert(id, sender);
-    Some(Listener { receiver })
+    let mut listeners = Listeners::<R>::new(None);
+    let (listener, entry) = Listener::new(&CHANNELS, remove_listener::<R>, false);
+    listeners.entries.push(entry);
+    channels.insert(id, UntypedBox::new(listeners));
+    Some(listener)
 }
 
-enum RequestReceiver<R: Request> {
-    Bounded(Receiver<RequestPair<R>>),
-    Unbounded(UnboundedReceiver<RequestPair<R>>),
-    Closed,
+/// Listen to request together with other shared listeners
+///
+/// Requests are routed among the shared listeners by the `strategy`
+/// of the first of them, the others' strategies are ignored
+/// while there are any shared listeners
+///
+/// Returns None if request is already listened by [listen]
+pub async fn listen_shared<R: Request>(strategy: Strategy<R>) -> Option<Listener<R>> {
+    let id = id!(R);
+    let mut channels = CHANNELS.write().await;
+    let listeners = channels
+        .entry(id)
+        .or_insert_with(|| UntypedBox::new(Listeners::<R>::new(Some(strategy))));
+    let listeners: &mut Listeners<R> = unsafe { listeners.get_mut() };
+    // exclusive listener is already registered
+    listeners.strategy?;
+    let (listener, entry) = Listener::new(&CHANNELS, remove_listener::<R>, true);
+    listeners.entries.push(entry);
+    Some(listener)
 }
 
 impl<R: Request> Listener<R> {
+    pub(super) fn new(
+        registry: &'static StaticTypeMap,
+        unregister: Unregister,
+        shared: bool,
+    ) -> (Self, ListenerEntry<R>) {
+        let queue = Arc::new(Queue::new(R::BUFFER_SIZE, R::STARVATION_LIMIT));
+        let id = LISTENER_ID.fetch_add(1, Ordering::Relaxed);
+        let queued = Arc::new(AtomicUsize::new(0));
+        let entry = ListenerEntry {
+            id,
+            queue: queue.clone(),
+            queued: queued.clone(),
+        };
+        let listener = Self {
+            id,
+            queue: Some(queue),
+            queued,
+            catch_panics: false,
+            shared,
+            registry,
+            unregister,
+        };
+        (listener, entry)
+    }
+
     /// Accepts next request for this Listener
     pub async fn accept<F, Fut>(&mut self, f: F)
     where
         F: FnOnce(R::Payload) -> Fut,
-        Fut: std::future::Future<Output = R::Response>,
+        Fut: Future<Output = R::Response>,
+    {
+        self.try_accept(|payload| {
+            let response = f(payload);
+            async move { Ok(response.await) }
+        })
+        .await
+    }
+
+    /// Accepts next request for this Listener
+    /// with a handler that can fail
+    ///
+    /// The error is received by the requester as
+    /// [RequestError::Handler](super::RequestError::Handler)
+    pub async fn try_accept<F, Fut>(&mut self, f: F)
+    where
+        F: FnOnce(R::Payload) -> Fut,
+        Fut: Future<Output = Result<R::Response, R::Error>>,
+    {
+        let (payload, responder) = self.recv().await;
+        let response = self.call(|| f(payload));
+        handle(responder, response, self.catch_panics).await
+    }
+
+    /// Accepts next request for this Listener
+    /// without responding to it
+    ///
+    /// The response can be sent later with the returned [Responder]
+    ///
+    /// Expired requests are skipped and fail with [RequestError::Expired]
+    ///
+    /// Returns None if the listener is closed
+    pub async fn next(&mut self) -> Option<(R::Payload, Responder<R>)> {
+        loop {
+            let request_pair = self.queue.as_ref()?.pop().await?;
+            if let Some(request) = self.receive(request_pair) {
+                return Some(request);
+            }
+        }
+    }
+
+    /// Accepts up to `max` queued requests for this Listener
+    /// and handles them with one call of the handler
+    ///
+    /// Waits for at least one request, the results are matched
+    /// to the requests by their order, the requests left without
+    /// the result fail with [RequestError::NotResponded]
+    ///
+    /// The errors are received by the requesters as
+    /// [RequestError::Handler](super::RequestError::Handler)
+    ///
+    /// Returns the number of the accepted requests, 0 if `max` is 0
+    pub async fn accept_batch<F, Fut>(&mut self, max: usize, f: F) -> usize
+    where
+        F: FnOnce(Vec<R::Payload>) -> Fut,
+        Fut: Future<Output = Vec<Result<R::Response, R::Error>>>,
     {
-        let request_pair = match &mut self.receiver {
-            RequestReceiver::Bounded(rx) => rx.recv().await,
-            RequestReceiver::Unbounded(rx) => rx.recv().await,
-            _ => unreachable!(),
+        if max == 0 {
+            return 0;
+        }
+        let (payload, responder) = self.recv().await;
+        let mut payloads = vec![payload];
+        let mut responders = vec![responder];
+        while payloads.len() < max {
+            let (payload, responder) = match self.try_next() {
+                Some(request) => request,
+                None => break,
+            };
+            payloads.push(payload);
+            responders.push(responder);
+        }
+        let count = payloads.len();
+        let result = match self.call(|| f(payloads)) {
+            Ok(responses) if self.catch_panics => CatchUnwind::new(responses).await,
+            Ok(responses) => Ok(responses.await),
+            Err(message) => Err(message),
         };
-        let request_pair = match request_pair {
-            Some(request_pair) => request_pair,
-            None => unreachable!(),
+        match result {
+            Ok(results) => {
+                for (responder, result) in responders.into_iter().zip(results) {
+                    let _ = responder.reply(result);
+                }
+            }
+            Err(message) => {
+                for responder in responders {
+                    responder.panicked(message.clone());
+                }
+            }
+        }
+        count
+    }
+
+    /// Accepts next request for this Listener
+    /// and handles it in a separate task
+    ///
+    /// Returns as soon as the request is accepted,
+    /// the response will be sent when the spawned task completes
+    pub async fn accept_spawn<F, Fut>(&mut self, f: F) -> JoinHandle<()>
+    where
+        F: FnOnce(R::Payload) -> Fut,
+        Fut: Future<Output = R::Response> + Send + 'static,
+    {
+        let (payload, responder) = self.recv().await;
+        let response = self.call(|| f(payload));
+        let response = response.map(|response| async move { Ok(response.await) });
+        tokio::spawn(handle(responder, response, self.catch_panics))
+    }
+
+    /// Handles requests for this Listener
+    /// with up to `concurrency` handlers running at once
+    ///
+    /// While all handlers are busy new requests are kept
+    /// in the request buffer
+    ///
+    /// # Panics
+    ///
+    /// Panics if `concurrency` is 0
+    pub async fn serve<F, Fut>(&mut self, concurrency: usize, f: F)
+    where
+        F: FnMut(R::Payload) -> Fut,
+        Fut: Future<Output = R::Response> + Send + 'static,
+    {
+        self.serve_until(concurrency, f, std::future::pending())
+            .await
+    }
+
+    /// Handles requests for this Listener
+    /// with up to `concurrency` handlers running at once
+    /// until `shutdown` completes
+    ///
+    /// After `shutdown` completes no more requests are accepted
+    /// and this method waits for the running handlers
+    ///
+    /// # Panics
+    ///
+    /// Panics if `concurrency` is 0
+    pub async fn serve_until<F, Fut, S>(&mut self, concurrency: usize, mut f: F, shutdown: S)
+    where
+        F: FnMut(R::Payload) -> Fut,
+        Fut: Future<Output = R::Response> + Send + 'static,
+        S: Future<Output = ()>,
+    {
+        assert!(
+            concurrency > 0,
+            "Listener::serve concurrency must be at least 1"
+        );
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+        tokio::pin!(shutdown);
+        loop {
+            let permit = tokio::select! {
+                biased;
+                _ = &mut shutdown => break,
+                permit = semaphore.clone().acquire_owned() => match permit {
+                    Ok(permit) => permit,
+                    Err(_) => unreachable!(),
+                },
+            };
+            let (payload, responder) = tokio::select! {
+                biased;
+                _ = &mut shutdown => break,
+                request = self.recv() => request,
+            };
+            let response = self.call(|| f(payload));
+            let response = response.map(|response| async move { Ok(response.await) });
+            let catch_panics = self.catch_panics;
+            tokio::spawn(async move {
+                handle(responder, response, catch_panics).await;
+                drop(permit);
+            });
+        }
+        let _ = semaphore.acquire_many(concurrency as u32).await;
+    }
+
+    /// Handles requests for this Listener by the tower `service`
+    ///
+    /// Each request is handled in a separate task as soon as
+    /// the service is ready, so the concurrency is controlled
+    /// by the service itself, e.g. by the concurrency limit layer.
+    /// The service errors are received by the requester as
+    /// [RequestError::Handler](super::RequestError::Handler)
+    ///
+    /// Runs until the service readiness fails and returns its error,
+    /// use [serve_service_until](Listener::serve_service_until) to stop it
+    ///
+    /// Available with the `tower` feature
+    #[cfg(feature = "tower")]
+    pub async fn serve_service<S>(&mut self, service: S) -> S::Error
+    where
+        S: tower_service::Service<R::Payload, Response = R::Response>,
+        S::Error: Into<R::Error>,
+        S::Future: Send + 'static,
+    {
+        match self
+            .serve_service_until(service, std::future::pending())
+            .await
+        {
+            Ok(()) => unreachable!(),
+            Err(e) => e,
+        }
+    }
+
+    /// Handles requests for this Listener by the tower `service`
+    /// until `shutdown` completes
+    ///
+    /// After `shutdown` completes no more requests are accepted
+    /// and this method waits for the running handlers
+    ///
+    /// Returns the error of the service readiness
+    ///
+    /// Available with the `tower` feature
+    #[cfg(feature = "tower")]
+    pub async fn serve_service_until<S, F>(
+        &mut self,
+        mut service: S,
+        shutdown: F,
+    ) -> Result<(), S::Error>
+    where
+        S: tower_service::Service<R::Payload, Response = R::Response>,
+        S::Error: Into<R::Error>,
+        S::Future: Send + 'static,
+        F: Future<Output = ()>,
+    {
+        // every running handler holds a sender
+        let (running, mut finished) = tokio::sync::mpsc::channel::<()>(1);
+        tokio::pin!(shutdown);
+        let result = loop {
+            let ready = tokio::select! {
+                biased;
+                _ = &mut shutdown => break Ok(()),
+                ready = super::service::Ready::new(&mut service) => ready,
+            };
+            if let Err(e) = ready {
+                break Err(e);
+            }
+            let (payload, responder) = tokio::select! {
+                biased;
+                _ = &mut shutdown => break Ok(()),
+                request = self.recv() => request,
+            };
+            let response = self.call(|| service.call(payload));
+            let response =
+                response.map(|response| async move { response.await.map_err(Into::into) });
+            let catch_panics = self.catch_panics;
+            let running = running.clone();
+            tokio::spawn(async move {
+                handle(responder, response, catch_panics).await;
+                drop(running);
+            });
         };
-        let response = f(request_pair.payload).await;
-        let _ = request_pair.responder.send(response);
+        drop(running);
+        let _ = finished.recv().await;
+        result
+    }
+
+    /// Enables or disables catching panics of the request handlers
+    ///
+    /// When enabled, a panicked handler is responded to the requester as
+    /// [RequestError::HandlerPanicked](super::RequestError::HandlerPanicked)
+    /// and the listener keeps accepting next requests
+    pub fn set_catch_panics(&mut self, catch_panics: bool) {
+        self.catch_panics = catch_panics;
     }
 
     /// Closes the listener
     ///
     /// Closing the listener with this method
     /// is preferable for performance reasons
+    ///
+    /// Requests queued for a shared listener
+    /// are routed to the other shared listeners
     pub async fn close(mut self) {
-        let receiver = mem::replace(&mut self.receiver, RequestReceiver::Closed);
-        match receiver {
-            RequestReceiver::Bounded(mut rx) => rx.close(),
-            RequestReceiver::Unbounded(mut rx) => rx.close(),
-            _ => unreachable!(),
+        let queue = match self.queue.take() {
+            Some(queue) => queue,
+            None => unreachable!(),
+        };
+        queue.close();
+        let mut queued = Vec::new();
+        while let Some(request_pair) = queue.try_pop() {
+            queued.push(request_pair);
         }
-        CHANNELS.write().await.remove(&id!(R));
+        let reroute = {
+            let mut channels = self.registry.write().await;
+            (self.unregister)(&mut channels, self.id)
+        };
+        if reroute {
+            for request_pair in queued {
+                // failed request pair drops its responder,
+                // so the requester receives NotResponded
+                let _ = dispatch(request_pair).await;
+            }
+        }
+    }
+
+    fn call<T>(&self, f: impl FnOnce() -> T) -> Result<T, String> {
+        if self.catch_panics {
+            catch_unwind(f)
+        } else {
+            Ok(f())
+        }
+    }
+
+    /// Accepts the queued request without waiting
+    fn try_next(&mut self) -> Option<(R::Payload, Responder<R>)> {
+        loop {
+            let request_pair = self.queue.as_ref()?.try_pop()?;
+            if let Some(request) = self.receive(request_pair) {
+                return Some(request);
+            }
+        }
+    }
+
+    /// Responds the expired and the rejected request with the error
+    fn receive(&self, request_pair: RequestPair<R>) -> Option<(R::Payload, Responder<R>)> {
+        let RequestPair {
+            mut payload,
+            responder,
+            ttl,
+            ..
+        } = request_pair;
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        if ttl.is_some_and(|ttl| !ttl.accept()) {
+            EXPIRED.increment(id!(R));
+            let _ = responder.send(Err(RequestError::Expired));
+            return None;
+        }
+        match intercept::<R>(Stage::Receive, &mut payload) {
+            Ok(()) => Some((payload, Responder::new(responder))),
+            // rejected request is responded with the rejection
+            Err(rejected) => {
+                let _ = responder.send(Err(rejected));
+                None
+            }
+        }
+    }
+
+    async fn recv(&mut self) -> (R::Payload, Responder<R>) {
+        match self.next().await {
+            Some(request) => request,
+            None => unreachable!(),
+        }
+    }
+}
+
+async fn handle<R, Fut>(responder: Responder<R>, response: Result<Fut, String>, catch_panics: bool)
+where
+    R: Request,
+    Fut: Future<Output = Result<R::Response, R::Error>>,
+{
+    let result = match response {
+        Ok(response) if catch_panics => CatchUnwind::new(response).await,
+        Ok(response) => Ok(response.await),
+        Err(message) => Err(message),
+    };
+    match result {
+        Ok(result) => {
+            let _ = responder.reply(result);
+        }
+        Err(message) => responder.panicked(message),
     }
 }
 
+fn remove_listener<R: Request>(channels: &mut HashMap<TypeId, UntypedBox>, id: usize) -> bool {
+    let type_id = id!(R);
+    let listeners = match channels.get_mut(&type_id) {
+        Some(listeners) => listeners,
+        None => return false,
+    };
+    let listeners: &mut Listeners<R> = unsafe { listeners.get_mut() };
+    listeners.entries.retain(|entry| entry.id != id);
+    let is_shared = listeners.strategy.is_some();
+    if listeners.entries.is_empty() {
+        channels.remove(&type_id);
+    }
+    is_shared
+}
+
 impl<R: Request> Drop for Listener<R> {
     fn drop(&mut self) {
-        match &mut self.receiver {
-            RequestReceiver::Bounded(rx) => rx.close(),
-            RequestReceiver::Unbounded(rx) => rx.close(),
-            RequestReceiver::Closed => return,
+        let queue = match self.queue.take() {
+            Some(queue) => queue,
+            None => return,
+        };
+        queue.close();
+        // the registry keeps the queue until it is updated,
+        // so the queued requests are taken out of it now
+        let mut queued = Vec::new();
+        while let Some(request_pair) = queue.try_pop() {
+            queued.push(request_pair);
+        }
+        let id = self.id;
+        let unregister = self.unregister;
+        self.registry.update_when_possible(move |channels| {
+            unregister(channels, id);
+        });
+        if self.shared && !queued.is_empty() {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                runtime.spawn(async move {
+                    for request_pair in queued {
+                        // this listener is closed, so it is skipped by the routing
+                        let _ = dispatch(request_pair).await;
+                    }
+                });
+            }
         }
-        CHANNELS.remove_when_possible(id!(R));
+        // otherwise the dropped request pairs drop their responders,
+        // so the requesters receive NotResponded
         #[cfg(debug_assertions)]
         eprintln!(
             "Listener for {} closed in slow manner! Use close method for optimize it",
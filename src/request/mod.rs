@@ -2,20 +2,56 @@
 //!
 //! Request-response communications
 
-use crate::common::StaticTypeMap;
-use tokio::sync::{
-    mpsc::{error::SendError, Sender, UnboundedSender},
-    oneshot,
+use crate::{
+    common::{expires_at, is_expired, ExpiryCounters, StaticTypeMap},
+    layer::{self, Kind, Stage},
+    priority::Priority,
+    retry::RetryPolicy,
+};
+use registry::Listeners;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc::error::SendError, oneshot},
+    time::Instant,
 };
 
+mod cache;
+mod circuit;
+mod gather;
+mod keyed;
 mod listener;
+mod registry;
+mod responder;
+#[cfg(feature = "tower")]
+mod service;
+mod single_flight;
+mod strategy;
 
 #[cfg(test)]
 mod test;
 
+pub use cache::*;
+pub use circuit::*;
+pub use gather::*;
+pub use keyed::*;
 pub use listener::*;
+pub use responder::*;
+#[cfg(feature = "tower")]
+pub use service::*;
+pub use single_flight::*;
+pub use strategy::*;
 
 static CHANNELS: StaticTypeMap = StaticTypeMap::new();
+static KEYED_CHANNELS: StaticTypeMap = StaticTypeMap::new();
+static EXPIRED: ExpiryCounters = ExpiryCounters::new();
 
 /// A request
 pub trait Request: Sized + 'static {
@@ -29,10 +65,47 @@ pub trait Request: Sized + 'static {
     const DEBUG_NAME: &'static str;
 
     /// Payload data type that will be sended with this request
-    type Payload: Send;
+    type Payload: Send + 'static;
 
     /// Response data type that will be responded from listener
     type Response: Send;
+
+    /// Error data type that can be responded from listener
+    /// instead of the response
+    ///
+    /// Use [Infallible](std::convert::Infallible) if the listener can not fail
+    type Error: Send;
+
+    /// Policy of retrying the [request] while it is not listened
+    const RETRY: Option<RetryPolicy> = None;
+
+    /// Collapsing of the concurrent identical [request]s into one
+    ///
+    /// Set to `Some(single_flight::<Self>)` to enable
+    const SINGLE_FLIGHT: Option<SingleFlight<Self>> = None;
+
+    /// Caching of the [request] responses
+    ///
+    /// Set to `Some(Cache::new(ttl, capacity, cached::<Self>))` to enable
+    const CACHE: Option<Cache<Self>> = None;
+
+    /// Circuit breaker failing the [request]s fast
+    /// while the listener keeps failing
+    const CIRCUIT_BREAKER: Option<CircuitBreaker> = None;
+
+    /// The number of the higher priority requests accepted
+    /// while a lower priority one is waiting,
+    /// after that the lower priority request is accepted,
+    /// the lane waiting the longest goes first
+    ///
+    /// None or `Some(0)` to always accept the higher priority first
+    const STARVATION_LIMIT: Option<u32> = None;
+
+    /// Time to live of the queued requests,
+    /// the requests not accepted in time fail with [RequestError::Expired]
+    ///
+    /// None to keep the requests queued until they are accepted
+    const TTL: Option<Duration> = None;
 }
 
 /// This enumeration is the list of the possible error outcomes for the
@@ -45,34 +118,324 @@ pub enum RequestError<R: Request> {
     SendError(R::Payload),
     /// Internal response channel is closed
     NotResponded,
+    /// Listener failed to handle the request
+    Handler(R::Error),
+    /// Listener panicked while handling the request
+    ///
+    /// Only reported by listeners with
+    /// [catching panics](crate::request::Listener::set_catch_panics)
+    HandlerPanicked {
+        /// The panic message
+        message: String,
+    },
+    /// Listener has not responded in time
+    TimedOut,
+    /// Request is rejected by a [Layer](crate::layer::Layer)
+    Rejected {
+        /// The reason of the rejection
+        reason: String,
+    },
+    /// Request is failed fast by the open
+    /// [CircuitBreaker](crate::request::CircuitBreaker)
+    CircuitOpen(R::Payload),
+    /// Request is not accepted by the listener in its time to live,
+    /// see [Request::TTL] and [request_with_ttl]
+    Expired,
 }
 
 /// Sends a payload to the [Listener](crate::request::Listener)
+///
+/// Retries by [Request::RETRY] policy if it is set
+///
+/// Shares the response among the concurrent identical requests
+/// if [Request::SINGLE_FLIGHT] is set
+///
+/// Responds from the cache if [Request::CACHE] is set
 pub async fn request<R: Request>(payload: R::Payload) -> Result<R::Response, RequestError<R>> {
-    let id = id!(R);
-    let channels = CHANNELS.read().await;
-    let sender = match channels.get(&id) {
-        Some(sender) => sender,
-        None => return Err(RequestError::NotListened(payload)),
-    };
+    request_with_priority::<R>(payload, Priority::Normal).await
+}
+
+/// Sends a payload with the priority to the [Listener](crate::request::Listener)
+///
+/// Higher priority requests are accepted first
+///
+/// Uses the same options as [request]
+pub async fn request_with_priority<R: Request>(
+    payload: R::Payload,
+    priority: Priority,
+) -> Result<R::Response, RequestError<R>> {
+    let mut payload = payload;
+    intercept::<R>(Stage::Send, &mut payload)?;
+    match R::CACHE {
+        Some(cache) => cache.request(payload, priority).await,
+        None => deliver::<R>(payload, priority).await,
+    }
+}
+
+/// Sends the intercepted payload by [Request::SINGLE_FLIGHT]
+/// if it is set
+async fn deliver<R: Request>(
+    payload: R::Payload,
+    priority: Priority,
+) -> Result<R::Response, RequestError<R>> {
+    match R::SINGLE_FLIGHT {
+        Some(single_flight) => single_flight(payload, priority).await,
+        None => send::<R>(payload, priority).await,
+    }
+}
+
+/// Sends the intercepted payload by [Request::RETRY] policy
+/// through [Request::CIRCUIT_BREAKER]
+async fn send<R: Request>(
+    payload: R::Payload,
+    priority: Priority,
+) -> Result<R::Response, RequestError<R>> {
+    send_by::<R, _, _>(payload, priority, dispatch::<R>).await
+}
+
+/// Sends the intercepted payload to the listener chosen by `dispatch`
+/// by [Request::RETRY] policy through [Request::CIRCUIT_BREAKER]
+async fn send_by<R, D, Fut>(
+    payload: R::Payload,
+    priority: Priority,
+    dispatch: D,
+) -> Result<R::Response, RequestError<R>>
+where
+    R: Request,
+    D: Fn(RequestPair<R>) -> Fut + Copy,
+    Fut: Future<Output = Result<(), RequestError<R>>>,
+{
+    circuit::guard::<R, _, _>(payload, |payload| async move {
+        match R::RETRY {
+            Some(policy) => retry_request::<R, _, _>(payload, &policy, priority, dispatch).await,
+            None => request_once::<R, _, _>(payload, priority, R::TTL, dispatch).await,
+        }
+    })
+    .await
+}
+
+/// Sends a payload to the [Listener](crate::request::Listener)
+///
+/// Retries by the `policy` while the request is not listened
+/// or its listener is closed
+///
+/// Unlike [request] ignores [Request::RETRY],
+/// [Request::SINGLE_FLIGHT] and [Request::CACHE]
+pub async fn request_with_retry<R: Request>(
+    payload: R::Payload,
+    policy: &RetryPolicy,
+) -> Result<R::Response, RequestError<R>> {
+    let mut payload = payload;
+    intercept::<R>(Stage::Send, &mut payload)?;
+    circuit::guard::<R, _, _>(payload, |payload| {
+        retry_request::<R, _, _>(payload, policy, Priority::Normal, dispatch::<R>)
+    })
+    .await
+}
+
+/// Sends a payload with the time to live to the [Listener](crate::request::Listener)
+///
+/// The request not accepted in `ttl` fails with [RequestError::Expired]
+///
+/// Unlike [request] ignores [Request::TTL], [Request::RETRY],
+/// [Request::SINGLE_FLIGHT] and [Request::CACHE]
+pub async fn request_with_ttl<R: Request>(
+    payload: R::Payload,
+    ttl: Duration,
+) -> Result<R::Response, RequestError<R>> {
+    let mut payload = payload;
+    intercept::<R>(Stage::Send, &mut payload)?;
+    circuit::guard::<R, _, _>(payload, |payload| {
+        request_once::<R, _, _>(payload, Priority::Normal, Some(ttl), dispatch::<R>)
+    })
+    .await
+}
+
+/// Returns the number of the requests expired in the listener queues
+///
+/// See [Request::TTL] and [request_with_ttl]
+pub fn expired_count<R: Request>() -> u64 {
+    EXPIRED.get(id!(R))
+}
+
+async fn retry_request<R, D, Fut>(
+    payload: R::Payload,
+    policy: &RetryPolicy,
+    priority: Priority,
+    dispatch: D,
+) -> Result<R::Response, RequestError<R>>
+where
+    R: Request,
+    D: Fn(RequestPair<R>) -> Fut + Copy,
+    Fut: Future<Output = Result<(), RequestError<R>>>,
+{
+    let mut payload = payload;
+    let mut retry = policy.start();
+    loop {
+        let result = request_once::<R, _, _>(payload, priority, R::TTL, dispatch).await;
+        let backoff = match &result {
+            Err(RequestError::NotListened(_)) | Err(RequestError::SendError(_)) => {
+                retry.next_backoff()
+            }
+            _ => None,
+        };
+        match (result, backoff) {
+            (Err(RequestError::NotListened(p)), Some(backoff))
+            | (Err(RequestError::SendError(p)), Some(backoff)) => {
+                payload = p;
+                tokio::time::sleep(backoff).await;
+            }
+            (result, _) => return result,
+        }
+    }
+}
+
+/// Passes the payload through the [layers](crate::layer)
+fn intercept<R: Request>(stage: Stage, payload: &mut R::Payload) -> Result<(), RequestError<R>> {
+    layer::apply::<R, _>(Kind::Request, stage, R::DEBUG_NAME, None, payload)
+        .map_err(|reason| RequestError::Rejected { reason })
+}
+
+async fn request_once<R, D, Fut>(
+    payload: R::Payload,
+    priority: Priority,
+    ttl: Option<Duration>,
+    dispatch: D,
+) -> Result<R::Response, RequestError<R>>
+where
+    R: Request,
+    D: Fn(RequestPair<R>) -> Fut,
+    Fut: Future<Output = Result<(), RequestError<R>>>,
+{
     let (tx, rx) = oneshot::channel();
+    let ttl = Ttl::new(ttl);
     let request_pair = RequestPair::<R> {
         payload,
         responder: tx,
+        priority,
+        ttl: ttl.clone(),
     };
-    if R::BUFFER_SIZE == 0 {
-        let sender: &UnboundedSender<_> = unsafe { sender.get_ref() };
-        sender.send(request_pair)?;
-    } else {
-        let sender: &Sender<_> = unsafe { sender.get_ref() };
-        sender.send(request_pair).await?;
+    dispatch(request_pair).await?;
+    receive_reply(rx, ttl).await
+}
+
+/// Waits for the reply,
+/// fails with [RequestError::Expired] if the request is not accepted in its `ttl`
+async fn receive_reply<R: Request>(
+    mut rx: oneshot::Receiver<Reply<R>>,
+    ttl: Option<Ttl>,
+) -> Reply<R> {
+    if let Some(ttl) = ttl {
+        match tokio::time::timeout_at(ttl.expires, &mut rx).await {
+            Ok(reply) => return reply.unwrap_or(Err(RequestError::NotResponded)),
+            // the abandoned request is skipped by the listener
+            Err(_) if ttl.abandon() => return Err(RequestError::Expired),
+            // the accepted request is handled regardless of its ttl
+            Err(_) => {}
+        }
+    }
+    rx.await.unwrap_or(Err(RequestError::NotResponded))
+}
+
+/// Sends a payload to the [Listener](crate::request::Listener)
+///
+/// Unlike [request] waits for the listener if the request is not listened
+pub async fn request_when_ready<R: Request>(
+    payload: R::Payload,
+) -> Result<R::Response, RequestError<R>> {
+    let mut payload = payload;
+    loop {
+        match request::<R>(payload).await {
+            Err(RequestError::NotListened(p)) => {
+                payload = p;
+                wait_listener::<R>().await;
+            }
+            result => return result,
+        }
     }
-    rx.await.map_err(|_| RequestError::NotResponded)
 }
 
+/// Waits until the request is listened
+pub async fn wait_listener<R: Request>() {
+    CHANNELS
+        .wait_until(id!(R), |listeners| {
+            let listeners: &Listeners<R> = unsafe { listeners.get_ref() };
+            listeners.open().next().is_some()
+        })
+        .await
+}
+
+/// Waits until the request is listened
+///
+/// Returns false if the request is not listened in `timeout`
+pub async fn wait_listener_timeout<R: Request>(timeout: Duration) -> bool {
+    tokio::time::timeout(timeout, wait_listener::<R>())
+        .await
+        .is_ok()
+}
+
+async fn dispatch<R: Request>(mut request_pair: RequestPair<R>) -> Result<(), RequestError<R>> {
+    let id = id!(R);
+    let channels = CHANNELS.read().await;
+    let listeners: &Listeners<R> = match channels.get(&id) {
+        Some(listeners) => unsafe { listeners.get_ref() },
+        None => return Err(RequestError::NotListened(request_pair.payload)),
+    };
+    loop {
+        let listener = match listeners.route(&request_pair.payload) {
+            Some(listener) => listener,
+            None => return Err(RequestError::NotListened(request_pair.payload)),
+        };
+        match listener.send(request_pair).await {
+            Ok(()) => return Ok(()),
+            // shared listener is closed meanwhile, so route to another one
+            Err(SendError(pair)) if listeners.strategy.is_some() => request_pair = pair,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+type Reply<R> = Result<<R as Request>::Response, RequestError<R>>;
+
+/// Future of the response of the request
+pub type ReplyFuture<R> = Pin<Box<dyn Future<Output = Reply<R>> + Send>>;
+
 struct RequestPair<R: Request> {
     payload: R::Payload,
-    responder: oneshot::Sender<R::Response>,
+    responder: oneshot::Sender<Reply<R>>,
+    priority: Priority,
+    /// None if the request never expires
+    ttl: Option<Ttl>,
+}
+
+/// Time to live of the queued request
+#[derive(Clone)]
+struct Ttl {
+    expires: Instant,
+    /// Set once the request is accepted by the listener
+    /// or abandoned by the requester
+    taken: Arc<AtomicBool>,
+}
+
+impl Ttl {
+    fn new(ttl: Option<Duration>) -> Option<Self> {
+        expires_at(ttl).map(|expires| Self {
+            expires,
+            taken: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Takes the request for the listener,
+    /// returns false if it is expired or abandoned
+    fn accept(&self) -> bool {
+        !is_expired(Some(self.expires)) && !self.taken.swap(true, Ordering::AcqRel)
+    }
+
+    /// Takes the request back from the queue,
+    /// returns false if it is already accepted
+    fn abandon(&self) -> bool {
+        !self.taken.swap(true, Ordering::AcqRel)
+    }
 }
 
 impl<R: Request> From<SendError<RequestPair<R>>> for RequestError<R> {
@@ -94,7 +457,48 @@ impl<R: Request> std::fmt::Debug for RequestError<R> {
             RequestError::NotResponded => {
                 write!(f, "RequestError in {}: NotResponded", R::DEBUG_NAME)?;
             }
+            RequestError::Handler(_) => {
+                write!(f, "RequestError in {}: Handler", R::DEBUG_NAME)?;
+            }
+            RequestError::HandlerPanicked { message } => {
+                write!(
+                    f,
+                    "RequestError in {}: HandlerPanicked: {}",
+                    R::DEBUG_NAME,
+                    message
+                )?;
+            }
+            RequestError::TimedOut => {
+                write!(f, "RequestError in {}: TimedOut", R::DEBUG_NAME)?;
+            }
+            RequestError::Rejected { reason } => {
+                write!(f, "RequestError in {}: Rejected: {}", R::DEBUG_NAME, reason)?;
+            }
+            RequestError::CircuitOpen(_) => {
+                write!(f, "RequestError in {}: CircuitOpen", R::DEBUG_NAME)?;
+            }
+            RequestError::Expired => {
+                write!(f, "RequestError in {}: Expired", R::DEBUG_NAME)?;
+            }
         }
         Ok(())
     }
 }
+
+impl<R: Request> std::fmt::Display for RequestError<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl<R: Request> std::error::Error for RequestError<R>
+where
+    R::Error: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RequestError::Handler(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use super::{Request, RequestPair, Strategy};
+use crate::common::{random, Queue};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::Hasher,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::mpsc::error::SendError;
+
+/// Listeners of the request stored in the registry
+pub(crate) struct Listeners<R: Request> {
+    /// None for the exclusive listener
+    pub(crate) strategy: Option<Strategy<R>>,
+    pub(crate) entries: Vec<ListenerEntry<R>>,
+    cursor: AtomicUsize,
+}
+
+pub(crate) struct ListenerEntry<R: Request> {
+    pub(crate) id: usize,
+    pub(crate) queue: Arc<Queue<RequestPair<R>>>,
+    pub(crate) queued: Arc<AtomicUsize>,
+}
+
+impl<R: Request> Listeners<R> {
+    pub(crate) fn new(strategy: Option<Strategy<R>>) -> Self {
+        Self {
+            strategy,
+            entries: Vec::new(),
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the listeners that are not closed
+    pub(crate) fn open(&self) -> impl Iterator<Item = &ListenerEntry<R>> + Clone {
+        self.entries.iter().filter(|entry| !entry.is_closed())
+    }
+
+    /// Chooses the listener for the request,
+    /// listeners that are closed are skipped
+    pub(crate) fn route(&self, payload: &R::Payload) -> Option<&ListenerEntry<R>> {
+        let strategy = match self.strategy {
+            Some(strategy) => strategy,
+            None => return self.entries.first(),
+        };
+        let mut open = self.entries.iter().filter(|entry| !entry.is_closed());
+        match strategy {
+            Strategy::RoundRobin => {
+                let len = self.entries.len();
+                let start = self.cursor.fetch_add(1, Ordering::Relaxed);
+                (0..len)
+                    .map(|i| &self.entries[start.wrapping_add(i) % len])
+                    .find(|entry| !entry.is_closed())
+            }
+            Strategy::LeastQueued => open.min_by_key(|entry| entry.queued.load(Ordering::Relaxed)),
+            Strategy::Random => {
+                let count = open.clone().count();
+                match count {
+                    0 => None,
+                    count => open.nth(random() as usize % count),
+                }
+            }
+            Strategy::Sticky(key) => {
+                // rendezvous hashing keeps most keys in place
+                // when the listeners come and go
+                let key = key(payload);
+                open.max_by_key(|entry| {
+                    let mut hasher = DefaultHasher::new();
+                    hasher.write_u64(key);
+                    hasher.write_usize(entry.id);
+                    hasher.finish()
+                })
+            }
+        }
+    }
+}
+
+impl<R: Request> Clone for ListenerEntry<R> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            queue: self.queue.clone(),
+            queued: self.queued.clone(),
+        }
+    }
+}
+
+impl<R: Request> ListenerEntry<R> {
+    pub(crate) fn is_closed(&self) -> bool {
+        self.queue.is_closed()
+    }
+
+    pub(crate) async fn send(
+        &self,
+        request_pair: RequestPair<R>,
+    ) -> Result<(), SendError<RequestPair<R>>> {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        // the cancelled or failed send is not queued
+        let mut pending = Pending(Some(&self.queued));
+        let priority = request_pair.priority;
+        let result = self.queue.push(request_pair, priority).await;
+        if result.is_ok() {
+            pending.0 = None;
+        }
+        result.map_err(SendError)
+    }
+}
+
+/// Decrements the queued counter on drop
+struct Pending<'a>(Option<&'a AtomicUsize>);
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if let Some(queued) = self.0 {
+            queued.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
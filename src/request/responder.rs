@@ -0,0 +1,74 @@
+use super::{Reply, Request, RequestError};
+use tokio::sync::oneshot;
+
+/// Responder for an accepted request
+///
+/// Allows to answer the request later, e.g. from another task
+pub struct Responder<R: Request> {
+    sender: oneshot::Sender<Reply<R>>,
+}
+
+impl<R: Request> Responder<R> {
+    pub(crate) fn new(sender: oneshot::Sender<Reply<R>>) -> Self {
+        Self { sender }
+    }
+
+    /// Sends the response to the requester
+    ///
+    /// Returns the response back if the requester is no longer waiting
+    pub fn respond(self, response: R::Response) -> Result<(), R::Response> {
+        match self.sender.send(Ok(response)) {
+            Ok(()) => Ok(()),
+            Err(Ok(response)) => Err(response),
+            Err(Err(_)) => unreachable!(),
+        }
+    }
+
+    /// Sends the error to the requester
+    ///
+    /// The requester receives it as [RequestError::Handler]
+    ///
+    /// Returns the error back if the requester is no longer waiting
+    pub fn fail(self, error: R::Error) -> Result<(), R::Error> {
+        match self.sender.send(Err(RequestError::Handler(error))) {
+            Ok(()) => Ok(()),
+            Err(Err(RequestError::Handler(error))) => Err(error),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Sends the response or the error to the requester
+    ///
+    /// Returns the result back if the requester is no longer waiting
+    pub fn reply(
+        self,
+        result: Result<R::Response, R::Error>,
+    ) -> Result<(), Result<R::Response, R::Error>> {
+        match result {
+            Ok(response) => self.respond(response).map_err(Ok),
+            Err(error) => self.fail(error).map_err(Err),
+        }
+    }
+
+    pub(crate) fn panicked(self, message: String) {
+        let _ = self
+            .sender
+            .send(Err(RequestError::HandlerPanicked { message }));
+    }
+
+    /// Checks if the requester is still waiting for the response
+    pub fn is_waiting(&self) -> bool {
+        !self.sender.is_closed()
+    }
+
+    /// Waits until the requester stops waiting for the response
+    pub async fn closed(&mut self) {
+        self.sender.closed().await
+    }
+}
+
+impl<R: Request> std::fmt::Debug for Responder<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Responder for {}", R::DEBUG_NAME)
+    }
+}
@@ -0,0 +1,82 @@
+use super::{request, Request, RequestError};
+use std::{
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower_service::Service;
+
+/// [Service] sending the requests to the [Listener](super::Listener)
+///
+/// Available with the `tower` feature
+pub struct Requester<R: Request> {
+    _request: PhantomData<fn() -> R>,
+}
+
+impl<R: Request> Requester<R> {
+    /// Creates the requester
+    pub fn new() -> Self {
+        Self {
+            _request: PhantomData,
+        }
+    }
+}
+
+impl<R: Request> Service<R::Payload> for Requester<R> {
+    type Response = R::Response;
+    type Error = RequestError<R>;
+    type Future = Pin<Box<dyn Future<Output = Result<R::Response, RequestError<R>>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, payload: R::Payload) -> Self::Future {
+        Box::pin(request::<R>(payload))
+    }
+}
+
+impl<R: Request> Clone for Requester<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R: Request> Copy for Requester<R> {}
+
+impl<R: Request> Default for Requester<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: Request> fmt::Debug for Requester<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Requester for {}", R::DEBUG_NAME)
+    }
+}
+
+/// Future waiting for the service readiness
+pub(super) struct Ready<'a, S, T> {
+    service: &'a mut S,
+    _request: PhantomData<fn(T)>,
+}
+
+impl<'a, S: Service<T>, T> Ready<'a, S, T> {
+    pub(super) fn new(service: &'a mut S) -> Self {
+        Self {
+            service,
+            _request: PhantomData,
+        }
+    }
+}
+
+impl<S: Service<T>, T> Future for Ready<'_, S, T> {
+    type Output = Result<(), S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.service.poll_ready(cx)
+    }
+}
@@ -0,0 +1,178 @@
+use super::{send, Reply, ReplyFuture, Request, RequestError};
+use crate::common::{StaticTypeMap, UntypedBox};
+use crate::priority::Priority;
+use parking_lot::Mutex;
+use std::{collections::HashMap, hash::Hash, sync::Arc};
+use tokio::sync::oneshot;
+
+static FLIGHTS: StaticTypeMap = StaticTypeMap::new();
+
+/// Sender of the request payload used by [Request::SINGLE_FLIGHT]
+pub type SingleFlight<R> = fn(<R as Request>::Payload, Priority) -> ReplyFuture<R>;
+
+/// Sends a payload to the [Listener](super::Listener)
+/// collapsing the concurrent identical requests into one
+///
+/// Only the first of the identical requests is delivered to the listener
+/// with its priority, the others wait for it and receive the clone of its response
+///
+/// It is intended to be set as [Request::SINGLE_FLIGHT],
+/// so the [request](super::request) uses it
+pub fn single_flight<R>(payload: R::Payload, priority: Priority) -> ReplyFuture<R>
+where
+    R: Request,
+    R::Payload: Hash + Eq + Clone,
+    R::Response: Clone,
+    R::Error: Clone,
+{
+    Box::pin(fly::<R>(payload, priority))
+}
+
+/// Waiters for the identical requests in flight
+type Flights<R> = Mutex<HashMap<<R as Request>::Payload, Vec<oneshot::Sender<Shared<R>>>>>;
+
+async fn fly<R>(payload: R::Payload, priority: Priority) -> Reply<R>
+where
+    R: Request,
+    R::Payload: Hash + Eq + Clone,
+    R::Response: Clone,
+    R::Error: Clone,
+{
+    let flights = flights::<R>().await;
+    loop {
+        let waiter = {
+            let mut flights = flights.lock();
+            match flights.get_mut(&payload) {
+                Some(waiters) => {
+                    let (tx, rx) = oneshot::channel();
+                    waiters.push(tx);
+                    Some(rx)
+                }
+                None => {
+                    flights.insert(payload.clone(), Vec::new());
+                    None
+                }
+            }
+        };
+        let waiter = match waiter {
+            Some(waiter) => waiter,
+            None => break,
+        };
+        match waiter.await {
+            Ok(shared) => return shared.into_reply(payload),
+            // the first request is cancelled, so try to become the first
+            Err(_) => continue,
+        }
+    }
+    let landing = Landing::<R> {
+        flights: &flights,
+        payload: Some(payload.clone()),
+    };
+    let reply = send::<R>(payload, priority).await;
+    for waiter in landing.land() {
+        let _ = waiter.send(Shared::new(&reply));
+    }
+    reply
+}
+
+async fn flights<R>() -> Arc<Flights<R>>
+where
+    R: Request,
+    R::Payload: Hash + Eq,
+{
+    let id = id!(R);
+    if let Some(flights) = FLIGHTS.read().await.get(&id) {
+        let flights: &Arc<Flights<R>> = unsafe { flights.get_ref() };
+        return flights.clone();
+    }
+    let mut channels = FLIGHTS.write().await;
+    let flights = channels
+        .entry(id)
+        .or_insert_with(|| UntypedBox::new(Arc::new(Flights::<R>::default())));
+    let flights: &Arc<Flights<R>> = unsafe { flights.get_ref() };
+    flights.clone()
+}
+
+/// Removes the request from the flights
+/// when it is completed or cancelled
+struct Landing<'a, R>
+where
+    R: Request,
+    R::Payload: Hash + Eq,
+{
+    flights: &'a Flights<R>,
+    payload: Option<R::Payload>,
+}
+
+impl<R> Landing<'_, R>
+where
+    R: Request,
+    R::Payload: Hash + Eq,
+{
+    fn land(mut self) -> Vec<oneshot::Sender<Shared<R>>> {
+        let payload = self.payload.take();
+        payload
+            .and_then(|payload| self.flights.lock().remove(&payload))
+            .unwrap_or_default()
+    }
+}
+
+impl<R> Drop for Landing<'_, R>
+where
+    R: Request,
+    R::Payload: Hash + Eq,
+{
+    fn drop(&mut self) {
+        // dropping the waiters wakes them up to retry
+        if let Some(payload) = self.payload.take() {
+            self.flights.lock().remove(&payload);
+        }
+    }
+}
+
+/// Reply shared with the waiting identical requests
+enum Shared<R: Request> {
+    Response(R::Response),
+    NotListened,
+    SendError,
+    CircuitOpen,
+    Error(RequestError<R>),
+}
+
+impl<R> Shared<R>
+where
+    R: Request,
+    R::Response: Clone,
+    R::Error: Clone,
+{
+    fn new(reply: &Reply<R>) -> Self {
+        match reply {
+            Ok(response) => Shared::Response(response.clone()),
+            Err(RequestError::NotListened(_)) => Shared::NotListened,
+            Err(RequestError::SendError(_)) => Shared::SendError,
+            Err(RequestError::CircuitOpen(_)) => Shared::CircuitOpen,
+            Err(RequestError::NotResponded) => Shared::Error(RequestError::NotResponded),
+            Err(RequestError::Handler(e)) => Shared::Error(RequestError::Handler(e.clone())),
+            Err(RequestError::HandlerPanicked { message }) => {
+                Shared::Error(RequestError::HandlerPanicked {
+                    message: message.clone(),
+                })
+            }
+            Err(RequestError::TimedOut) => Shared::Error(RequestError::TimedOut),
+            Err(RequestError::Expired) => Shared::Error(RequestError::Expired),
+            Err(RequestError::Rejected { reason }) => Shared::Error(RequestError::Rejected {
+                reason: reason.clone(),
+            }),
+        }
+    }
+
+    fn into_reply(self, payload: R::Payload) -> Reply<R> {
+        match self {
+            Shared::Response(response) => Ok(response),
+            Shared::NotListened => Err(RequestError::NotListened(payload)),
+            Shared::SendError => Err(RequestError::SendError(payload)),
+            Shared::CircuitOpen => Err(RequestError::CircuitOpen(payload)),
+            Shared::Error(e) => Err(e),
+        }
+    }
+}
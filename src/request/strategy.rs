@@ -0,0 +1,35 @@
+use super::Request;
+
+/// Strategy of routing requests among the shared listeners
+///
+/// See [listen_shared](crate::request::listen_shared)
+pub enum Strategy<R: Request> {
+    /// Sends requests to listeners in turn
+    RoundRobin,
+    /// Sends each request to the listener with the fewest queued requests
+    LeastQueued,
+    /// Sends each request to a random listener
+    Random,
+    /// Sends requests with the same key to the same listener
+    /// while it is listening
+    Sticky(fn(&R::Payload) -> u64),
+}
+
+impl<R: Request> Clone for Strategy<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R: Request> Copy for Strategy<R> {}
+
+impl<R: Request> std::fmt::Debug for Strategy<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Strategy::RoundRobin => write!(f, "RoundRobin"),
+            Strategy::LeastQueued => write!(f, "LeastQueued"),
+            Strategy::Random => write!(f, "Random"),
+            Strategy::Sticky(_) => write!(f, "Sticky"),
+        }
+    }
+}
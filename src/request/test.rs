@@ -1,5 +1,5 @@
 use super::*;
-use std::sync::Arc;
+use std::{convert::Infallible, sync::Arc};
 use tokio::sync::Notify;
 
 struct SumRequest;
@@ -7,6 +7,7 @@ struct SumRequest;
 impl Request for SumRequest {
     type Payload = (i32, i32);
     type Response = i32;
+    type Error = Infallible;
     const BUFFER_SIZE: usize = 2;
     const DEBUG_NAME: &'static str = "SumRequest";
 }
@@ -22,6 +23,7 @@ async fn sum_listener(request_count: usize, ready: Arc<Notify>) {
 }
 
 #[tokio::test]
+#[allow(clippy::redundant_pattern_matching)]
 async fn sum_request() {
     let ready = Arc::new(Notify::new());
     println!("sum_request: Start listener");
@@ -39,3 +41,935 @@ async fn sum_request() {
     println!("sum_request: Join listener");
     l.await.unwrap();
 }
+
+crate::declare! {
+    request[4] ConcurrentRequest(i32) -> i32;
+}
+
+#[tokio::test]
+async fn concurrent_serve() {
+    let ready = Arc::new(Notify::new());
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    println!("concurrent_serve: Start listener");
+    let l = tokio::spawn({
+        let ready = ready.clone();
+        async move {
+            let mut listener = listen::<ConcurrentRequest>().await.unwrap();
+            ready.notify_one();
+            // both handlers must run at once to pass the barrier
+            let barrier = Arc::new(tokio::sync::Barrier::new(2));
+            listener
+                .serve_until(
+                    2,
+                    move |n| {
+                        let barrier = barrier.clone();
+                        async move {
+                            barrier.wait().await;
+                            n * 2
+                        }
+                    },
+                    async {
+                        let _ = stop_rx.await;
+                    },
+                )
+                .await;
+            listener.close().await;
+        }
+    });
+    ready.notified().await;
+
+    println!("concurrent_serve: Send 2 parallel requests");
+    let (a, b) = tokio::join!(ConcurrentRequest::request(1), ConcurrentRequest::request(2));
+    assert_eq!(a.unwrap(), 2);
+    assert_eq!(b.unwrap(), 4);
+
+    println!("concurrent_serve: Stop listener");
+    stop_tx.send(()).unwrap();
+    l.await.unwrap();
+    assert!(ConcurrentRequest::request(0).await.is_err());
+}
+
+crate::declare! {
+    request DeferredRequest(i32) -> i32;
+}
+
+#[tokio::test]
+async fn deferred_responders() {
+    println!("deferred_responders: Listen");
+    let mut listener = listen::<DeferredRequest>().await.unwrap();
+
+    println!("deferred_responders: Send 2 requests");
+    let r1 = tokio::spawn(DeferredRequest::request(1));
+    let (p1, responder1) = listener.next().await.unwrap();
+    let r2 = tokio::spawn(DeferredRequest::request(2));
+    let (p2, responder2) = listener.next().await.unwrap();
+    assert!(responder1.is_waiting());
+    assert!(responder2.is_waiting());
+
+    println!("deferred_responders: Respond in reverse order");
+    responder2.respond(p2 * 10).unwrap();
+    assert_eq!(r2.await.unwrap().unwrap(), 20);
+    responder1.respond(p1 * 10).unwrap();
+    assert_eq!(r1.await.unwrap().unwrap(), 10);
+
+    println!("deferred_responders: Respond to gone requester");
+    let r3 = tokio::spawn(DeferredRequest::request(3));
+    let (_, mut responder3) = listener.next().await.unwrap();
+    r3.abort();
+    responder3.closed().await;
+    assert!(!responder3.is_waiting());
+    assert_eq!(responder3.respond(30), Err(30));
+
+    listener.close().await;
+}
+
+crate::declare! {
+    request DivRequest((i32, i32)) -> i32 throws &'static str;
+}
+
+async fn div_listener(request_count: usize, ready: Arc<Notify>) {
+    println!("Listen: Div");
+    let mut listener = listen::<DivRequest>().await.unwrap();
+    ready.notify_one();
+    for i in 0..request_count {
+        println!("Accept: Div #{}", i);
+        listener
+            .try_accept(|(a, b)| async move {
+                match b {
+                    0 => Err("division by zero"),
+                    b => Ok(a / b),
+                }
+            })
+            .await;
+    }
+    listener.close().await;
+}
+
+#[tokio::test]
+async fn handler_errors() {
+    let ready = Arc::new(Notify::new());
+    println!("handler_errors: Start listener");
+    let l = tokio::spawn(div_listener(2, ready.clone()));
+    ready.notified().await;
+
+    println!("handler_errors: Send 2 requests");
+    assert_eq!(DivRequest::request((6, 3)).await.unwrap(), 2);
+    assert!(matches!(
+        DivRequest::request((6, 0)).await,
+        Err(RequestError::Handler("division by zero"))
+    ));
+
+    println!("handler_errors: Join listener");
+    l.await.unwrap();
+}
+
+crate::declare! {
+    request PanicRequest(i32) -> i32;
+}
+
+async fn panic_listener(ready: Arc<Notify>) {
+    println!("Listen: Panic");
+    let mut listener = listen::<PanicRequest>().await.unwrap();
+    listener.set_catch_panics(true);
+    ready.notify_one();
+    for i in 0..3 {
+        println!("Accept: Panic #{}", i);
+        listener
+            .accept(|n| {
+                if n < 0 {
+                    panic!("negative payload");
+                }
+                async move {
+                    if n == 0 {
+                        panic!("zero payload");
+                    }
+                    n
+                }
+            })
+            .await;
+    }
+    listener.close().await;
+}
+
+#[tokio::test]
+async fn catch_handler_panics() {
+    let ready = Arc::new(Notify::new());
+    println!("catch_handler_panics: Start listener");
+    let l = tokio::spawn(panic_listener(ready.clone()));
+    ready.notified().await;
+
+    println!("catch_handler_panics: Send 3 requests");
+    match PanicRequest::request(-1).await {
+        Err(RequestError::HandlerPanicked { message }) => assert_eq!(message, "negative payload"),
+        r => panic!("unexpected result: {:?}", r),
+    }
+    match PanicRequest::request(0).await {
+        Err(RequestError::HandlerPanicked { message }) => assert_eq!(message, "zero payload"),
+        r => panic!("unexpected result: {:?}", r),
+    }
+    assert_eq!(PanicRequest::request(1).await.unwrap(), 1);
+
+    println!("catch_handler_panics: Join listener");
+    l.await.unwrap();
+}
+
+crate::declare! {
+    request SharedRequest(u64) -> usize;
+}
+
+async fn shared_listener(listener: Listener<SharedRequest>, n: usize, stop: Arc<Notify>) {
+    let mut listener = listener;
+    loop {
+        tokio::select! {
+            _ = stop.notified() => break,
+            _ = listener.accept(|_| async move { n }) => {}
+        }
+    }
+    listener.close().await;
+}
+
+#[tokio::test]
+async fn shared_listeners() {
+    println!("shared_listeners: Listen with 2 shared listeners");
+    let l1 = listen_shared::<SharedRequest>(Strategy::RoundRobin)
+        .await
+        .unwrap();
+    let l2 = listen_shared::<SharedRequest>(Strategy::RoundRobin)
+        .await
+        .unwrap();
+    assert!(listen::<SharedRequest>().await.is_none());
+    let stop1 = Arc::new(Notify::new());
+    let stop2 = Arc::new(Notify::new());
+    let j1 = tokio::spawn(shared_listener(l1, 1, stop1.clone()));
+    let j2 = tokio::spawn(shared_listener(l2, 2, stop2.clone()));
+
+    println!("shared_listeners: Send requests in round robin");
+    let mut responses = Vec::new();
+    for i in 0..4 {
+        responses.push(SharedRequest::request(i).await.unwrap());
+    }
+    responses.sort_unstable();
+    assert_eq!(responses, vec![1, 1, 2, 2]);
+
+    println!("shared_listeners: Close listener #1");
+    stop1.notify_one();
+    j1.await.unwrap();
+    for i in 0..2 {
+        assert_eq!(SharedRequest::request(i).await.unwrap(), 2);
+    }
+
+    println!("shared_listeners: Close listener #2");
+    stop2.notify_one();
+    j2.await.unwrap();
+    assert!(matches!(
+        SharedRequest::request(0).await,
+        Err(RequestError::NotListened(0))
+    ));
+}
+
+crate::declare! {
+    request StickyRequest(u64) -> usize;
+}
+
+#[tokio::test]
+async fn sticky_shared_listeners() {
+    println!("sticky_shared_listeners: Listen with 3 shared listeners");
+    let mut listeners = Vec::new();
+    for _ in 0..3 {
+        let listener = listen_shared::<StickyRequest>(Strategy::Sticky(|key| *key))
+            .await
+            .unwrap();
+        listeners.push(listener);
+    }
+
+    println!("sticky_shared_listeners: Send requests with the same keys");
+    for key in 0..8 {
+        let mut chosen = None;
+        for _ in 0..3 {
+            let r = tokio::spawn(StickyRequest::request(key));
+            let mut accepted = None;
+            while accepted.is_none() {
+                for (n, listener) in listeners.iter_mut().enumerate() {
+                    tokio::select! {
+                        biased;
+                        request = listener.next() => {
+                            let (_, responder) = request.unwrap();
+                            responder.respond(n).unwrap();
+                            accepted = Some(n);
+                            break;
+                        }
+                        _ = tokio::task::yield_now() => {}
+                    }
+                }
+            }
+            let n = r.await.unwrap().unwrap();
+            assert_eq!(accepted, Some(n));
+            assert_eq!(*chosen.get_or_insert(n), n);
+        }
+    }
+
+    for listener in listeners {
+        listener.close().await;
+    }
+}
+
+crate::declare! {
+    request GatherRequest(i32) -> i32 throws &'static str;
+}
+
+#[tokio::test]
+async fn gather_responses() {
+    use std::time::Duration;
+
+    let timeout = Duration::from_millis(100);
+    assert!(matches!(
+        GatherRequest::request(0).await,
+        Err(RequestError::NotListened(0))
+    ));
+    assert!(matches!(
+        first_of::<GatherRequest>(0, timeout).await,
+        Err(RequestError::NotListened(0))
+    ));
+
+    println!("gather_responses: Listen with 3 shared listeners");
+    let mut listeners = Vec::new();
+    for _ in 0..3 {
+        let listener = listen_shared::<GatherRequest>(Strategy::RoundRobin)
+            .await
+            .unwrap();
+        listeners.push(listener);
+    }
+    let mut silent = listeners.pop().unwrap();
+    let mut failing = listeners.pop().unwrap();
+    let mut working = listeners.pop().unwrap();
+    let l = tokio::spawn(async move {
+        let mut held = Vec::new();
+        loop {
+            tokio::select! {
+                _ = working.accept(|n| async move { n * 2 }) => {}
+                _ = failing.try_accept(|_| async { Err("failed") }) => {}
+                // hold the responder so the request is never answered
+                Some(request) = silent.next() => held.push(request),
+            }
+        }
+    });
+
+    println!("gather_responses: Gather all");
+    let mut replies = gather::<GatherRequest>(5, timeout).await;
+    assert_eq!(replies.len(), 3);
+    replies.sort_by_key(|reply| match reply {
+        Ok(_) => 0,
+        Err(RequestError::Handler(_)) => 1,
+        Err(RequestError::TimedOut) => 2,
+        Err(_) => 3,
+    });
+    assert!(matches!(replies[0], Ok(10)));
+    assert!(matches!(replies[1], Err(RequestError::Handler("failed"))));
+    assert!(matches!(replies[2], Err(RequestError::TimedOut)));
+
+    println!("gather_responses: First of all");
+    assert_eq!(first_of::<GatherRequest>(5, timeout).await.unwrap(), 10);
+
+    println!("gather_responses: Quorum");
+    assert_eq!(
+        quorum::<GatherRequest>(5, 1, timeout).await.unwrap(),
+        vec![10]
+    );
+    assert!(matches!(
+        quorum::<GatherRequest>(5, 2, timeout).await,
+        Err(RequestError::TimedOut)
+    ));
+    assert!(matches!(
+        quorum::<GatherRequest>(5, 4, timeout).await,
+        Err(RequestError::NotListened(5))
+    ));
+
+    l.abort();
+}
+
+crate::declare! {
+    request[1] KeyedRequest(i32) -> String;
+}
+
+async fn keyed_listener(listener: Listener<KeyedRequest>, name: &'static str, count: usize) {
+    let mut listener = listener;
+    for _ in 0..count {
+        listener
+            .accept(|n| async move { format!("{}: {}", name, n) })
+            .await;
+    }
+    listener.close().await;
+}
+
+#[tokio::test]
+async fn keyed_listeners() {
+    println!("keyed_listeners: Listen with 2 keys");
+    let a = listen_keyed::<KeyedRequest, _>("a").await.unwrap();
+    let b = listen_keyed::<KeyedRequest, _>("b").await.unwrap();
+    assert!(listen_keyed::<KeyedRequest, _>("a").await.is_none());
+    let mut keys = listened_keys::<KeyedRequest, &str>().await;
+    keys.sort_unstable();
+    assert_eq!(keys, vec!["a", "b"]);
+    let la = tokio::spawn(keyed_listener(a, "a", 1));
+    let lb = tokio::spawn(keyed_listener(b, "b", 2));
+
+    println!("keyed_listeners: Send requests by keys");
+    assert_eq!(
+        request_keyed::<KeyedRequest, _>(&"b", 1).await.unwrap(),
+        "b: 1"
+    );
+    assert_eq!(
+        request_keyed::<KeyedRequest, _>(&"a", 2).await.unwrap(),
+        "a: 2"
+    );
+    assert!(matches!(
+        request_keyed::<KeyedRequest, _>(&"c", 3).await,
+        Err(RequestError::NotListened(3))
+    ));
+    assert!(matches!(
+        KeyedRequest::request(4).await,
+        Err(RequestError::NotListened(4))
+    ));
+
+    println!("keyed_listeners: Join listener a");
+    la.await.unwrap();
+    assert_eq!(listened_keys::<KeyedRequest, &str>().await, vec!["b"]);
+    assert_eq!(
+        request_keyed::<KeyedRequest, _>(&"b", 5).await.unwrap(),
+        "b: 5"
+    );
+    lb.await.unwrap();
+    assert!(listened_keys::<KeyedRequest, &str>().await.is_empty());
+}
+
+crate::declare! {
+    request LateRequest(i32) -> i32;
+}
+
+#[tokio::test]
+async fn wait_for_listener() {
+    use std::time::Duration;
+
+    println!("wait_for_listener: Wait without listener");
+    assert!(!wait_listener_timeout::<LateRequest>(Duration::from_millis(10)).await);
+    assert!(matches!(
+        LateRequest::request(21).await,
+        Err(RequestError::NotListened(21))
+    ));
+
+    println!("wait_for_listener: Request before listener");
+    let r = tokio::spawn(request_when_ready::<LateRequest>(21));
+    tokio::task::yield_now().await;
+    let w = tokio::spawn(wait_listener::<LateRequest>());
+
+    println!("wait_for_listener: Start listener");
+    let mut listener = listen::<LateRequest>().await.unwrap();
+    w.await.unwrap();
+    listener.accept(|n| async move { n * 2 }).await;
+    assert_eq!(r.await.unwrap().unwrap(), 42);
+    listener.close().await;
+}
+
+crate::declare! {
+    request RetryRequest(i32) -> i32 with retry(
+        crate::retry::RetryPolicy::fixed(Duration::from_millis(5)).with_max_attempts(40)
+    );
+}
+
+#[tokio::test]
+async fn retry_request() {
+    use crate::retry::RetryPolicy;
+    use std::time::Duration;
+
+    println!("retry_request: Exhaust retries");
+    let policy = RetryPolicy::fixed(Duration::from_millis(1)).with_max_attempts(3);
+    assert!(matches!(
+        request_with_retry::<RetryRequest>(1, &policy).await,
+        Err(RequestError::NotListened(1))
+    ));
+
+    println!("retry_request: Request before listener");
+    let r = tokio::spawn(RetryRequest::request(2));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let mut listener = listen::<RetryRequest>().await.unwrap();
+    listener.accept(|n| async move { n * 2 }).await;
+    assert_eq!(r.await.unwrap().unwrap(), 4);
+    listener.close().await;
+}
+
+#[cfg(feature = "tower")]
+crate::declare! {
+    request TowerRequest(i32) -> i32 throws OddError;
+}
+
+#[cfg(feature = "tower")]
+#[derive(Debug, PartialEq)]
+struct OddError(i32);
+
+#[cfg(feature = "tower")]
+impl std::fmt::Display for OddError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is odd", self.0)
+    }
+}
+
+#[cfg(feature = "tower")]
+impl std::error::Error for OddError {}
+
+#[cfg(feature = "tower")]
+struct Halve;
+
+#[cfg(feature = "tower")]
+impl tower_service::Service<i32> for Halve {
+    type Response = i32;
+    type Error = OddError;
+    type Future = std::future::Ready<Result<i32, OddError>>;
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), OddError>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, n: i32) -> Self::Future {
+        std::future::ready(match n % 2 {
+            0 => Ok(n / 2),
+            _ => Err(OddError(n)),
+        })
+    }
+}
+
+#[cfg(feature = "tower")]
+#[tokio::test]
+async fn tower_service() {
+    use tower_service::Service;
+
+    println!("tower_service: Serve the service");
+    let mut listener = listen::<TowerRequest>().await.unwrap();
+    let shutdown = Arc::new(Notify::new());
+    let h = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            let result = listener
+                .serve_service_until(Halve, shutdown.notified())
+                .await;
+            assert!(result.is_ok());
+            listener
+        }
+    });
+
+    println!("tower_service: Request without the requester");
+    assert_eq!(TowerRequest::request(4).await.unwrap(), 2);
+
+    println!("tower_service: Request with the requester");
+    let mut requester = Requester::<TowerRequest>::new();
+    assert_eq!(requester.call(4).await.unwrap(), 2);
+    match requester.call(3).await {
+        Err(RequestError::Handler(e)) => assert_eq!(e, OddError(3)),
+        r => panic!("Unexpected result: {:?}", r),
+    }
+
+    println!("tower_service: Request through the timeout layer");
+    let mut timeout = tower::timeout::Timeout::new(requester, Duration::from_millis(100));
+    assert_eq!(timeout.call(6).await.unwrap(), 3);
+    let error = timeout.call(5).await.unwrap_err();
+    let error = error.downcast::<RequestError<TowerRequest>>().unwrap();
+    assert_eq!(error.to_string(), "RequestError in TowerRequest: Handler");
+    assert_eq!(
+        std::error::Error::source(&*error).unwrap().to_string(),
+        "5 is odd"
+    );
+
+    println!("tower_service: Shut down the listener");
+    shutdown.notify_one();
+    let listener = h.await.unwrap();
+    listener.close().await;
+    let error = timeout.call(8).await.unwrap_err();
+    assert!(error.is::<RequestError<TowerRequest>>());
+}
+
+#[cfg(feature = "tower")]
+crate::declare! {
+    request SlowTowerRequest(i32) -> i32 throws OddError;
+}
+
+#[cfg(feature = "tower")]
+#[tokio::test]
+async fn tower_timeout() {
+    use tower_service::Service;
+
+    tokio::time::pause();
+    println!("tower_timeout: Request the slow listener through the timeout layer");
+    let mut listener = listen::<SlowTowerRequest>().await.unwrap();
+    let mut timeout = tower::timeout::Timeout::new(
+        Requester::<SlowTowerRequest>::new(),
+        Duration::from_millis(50),
+    );
+    let request = tokio::spawn(async move { timeout.call(1).await });
+    let (_, responder) = listener.next().await.unwrap();
+    let error = request.await.unwrap().unwrap_err();
+    assert!(error.is::<tower::timeout::error::Elapsed>());
+    drop(responder);
+
+    println!("tower_timeout: The listener still serves the other requests");
+    let request = tokio::spawn(SlowTowerRequest::request(2));
+    let (payload, responder) = listener.next().await.unwrap();
+    responder.respond(payload / 2).unwrap();
+    assert_eq!(request.await.unwrap().unwrap(), 1);
+    listener.close().await;
+}
+
+crate::declare! {
+    request FlightRequest(i32) -> i32 with single_flight;
+}
+
+#[tokio::test]
+async fn single_flight_requests() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    println!("single_flight_requests: Not listened");
+    assert!(matches!(
+        FlightRequest::request(1).await,
+        Err(RequestError::NotListened(1))
+    ));
+
+    println!("single_flight_requests: Send identical requests");
+    let mut listener = listen::<FlightRequest>().await.unwrap();
+    let requests: Vec<_> = [2, 2, 2, 3]
+        .into_iter()
+        .map(|n| tokio::spawn(FlightRequest::request(n)))
+        .collect();
+    let delivered = Arc::new(AtomicUsize::new(0));
+    let h = tokio::spawn({
+        let delivered = delivered.clone();
+        async move {
+            listener
+                .serve(4, move |n| {
+                    delivered.fetch_add(1, Ordering::Relaxed);
+                    async move {
+                        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                        n * 10
+                    }
+                })
+                .await
+        }
+    });
+
+    let mut responses = Vec::new();
+    for r in requests {
+        responses.push(r.await.unwrap().unwrap());
+    }
+    assert_eq!(responses, [20, 20, 20, 30]);
+    assert_eq!(delivered.load(Ordering::Relaxed), 2);
+    h.abort();
+}
+
+crate::declare! {
+    request CachedRequest(i32) -> i32 with cached(Duration::from_secs(60), 2);
+}
+
+#[tokio::test]
+async fn cached_requests() {
+    println!("cached_requests: Not listened is not cached");
+    assert!(matches!(
+        CachedRequest::request(1).await,
+        Err(RequestError::NotListened(1))
+    ));
+
+    let mut listener = listen::<CachedRequest>().await.unwrap();
+    let h = tokio::spawn(async move {
+        let mut counter = 0;
+        loop {
+            listener
+                .accept(|n| {
+                    counter += 1;
+                    async move { n * 100 + counter }
+                })
+                .await;
+        }
+    });
+
+    println!("cached_requests: Respond from the cache");
+    assert_eq!(CachedRequest::request(1).await.unwrap(), 101);
+    assert_eq!(CachedRequest::request(1).await.unwrap(), 101);
+    assert_eq!(CachedRequest::request(2).await.unwrap(), 202);
+    assert_eq!(
+        cache_stats::<CachedRequest>().await,
+        CacheStats {
+            hits: 1,
+            misses: 3,
+            len: 2
+        }
+    );
+
+    println!("cached_requests: Evict the least recently used");
+    assert_eq!(CachedRequest::request(1).await.unwrap(), 101);
+    assert_eq!(CachedRequest::request(3).await.unwrap(), 303);
+    assert_eq!(CachedRequest::request(1).await.unwrap(), 101);
+    assert_eq!(CachedRequest::request(2).await.unwrap(), 204);
+
+    println!("cached_requests: Invalidate");
+    assert!(invalidate::<CachedRequest>(&1).await);
+    assert!(!invalidate::<CachedRequest>(&1).await);
+    assert_eq!(CachedRequest::request(1).await.unwrap(), 105);
+    invalidate_all::<CachedRequest>().await;
+    assert_eq!(cache_stats::<CachedRequest>().await.len, 0);
+    h.abort();
+}
+
+crate::declare! {
+    request BreakerRequest(i32) -> i32 throws String
+        with circuit_breaker(CircuitBreaker::new(2, Duration::from_millis(50)));
+}
+
+#[tokio::test]
+async fn circuit_breaker() {
+    println!("circuit_breaker: Listen");
+    let mut listener = listen::<BreakerRequest>().await.unwrap();
+    let h = tokio::spawn(async move {
+        loop {
+            listener
+                .try_accept(|n| async move {
+                    match n {
+                        0 => Err("zero".to_string()),
+                        n => Ok(n),
+                    }
+                })
+                .await;
+        }
+    });
+
+    println!("circuit_breaker: Open after failures");
+    assert_eq!(circuit_state::<BreakerRequest>(), CircuitState::Closed);
+    assert!(BreakerRequest::request(0).await.is_err());
+    assert_eq!(circuit_state::<BreakerRequest>(), CircuitState::Closed);
+    assert!(BreakerRequest::request(0).await.is_err());
+    assert_eq!(circuit_state::<BreakerRequest>(), CircuitState::Open);
+    assert!(matches!(
+        BreakerRequest::request(1).await,
+        Err(RequestError::CircuitOpen(1))
+    ));
+
+    println!("circuit_breaker: Failed probe opens again");
+    tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+    assert_eq!(circuit_state::<BreakerRequest>(), CircuitState::HalfOpen);
+    assert!(matches!(
+        BreakerRequest::request(0).await,
+        Err(RequestError::Handler(_))
+    ));
+    assert_eq!(circuit_state::<BreakerRequest>(), CircuitState::Open);
+
+    println!("circuit_breaker: Successful probe closes");
+    tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+    assert_eq!(BreakerRequest::request(1).await.unwrap(), 1);
+    assert_eq!(circuit_state::<BreakerRequest>(), CircuitState::Closed);
+    h.abort();
+}
+
+crate::declare! {
+    request PriorityRequest(i32) -> i32;
+}
+
+#[tokio::test]
+async fn priority_requests() {
+    use crate::priority::Priority;
+
+    println!("priority_requests: Queue requests before accepting");
+    let mut listener = listen::<PriorityRequest>().await.unwrap();
+    let low = tokio::spawn(request_with_priority::<PriorityRequest>(1, Priority::Low));
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    let normal = tokio::spawn(PriorityRequest::request(3));
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    let high = tokio::spawn(request_with_priority::<PriorityRequest>(2, Priority::High));
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+    println!("priority_requests: High priority is accepted first");
+    for expected in [2, 3, 1] {
+        let (payload, responder) = listener.next().await.unwrap();
+        assert_eq!(payload, expected);
+        responder.respond(payload * 10).unwrap();
+    }
+    assert_eq!(high.await.unwrap().unwrap(), 20);
+    assert_eq!(normal.await.unwrap().unwrap(), 30);
+    assert_eq!(low.await.unwrap().unwrap(), 10);
+    listener.close().await;
+}
+
+crate::declare! {
+    request ExpiringRequest(i32) -> i32 with ttl(Duration::from_millis(50));
+}
+
+#[tokio::test]
+async fn expired_requests() {
+    tokio::time::pause();
+    println!("expired_requests: Queue requests before accepting");
+    let mut listener = listen::<ExpiringRequest>().await.unwrap();
+    let expired = tokio::spawn(ExpiringRequest::request(1));
+    let long_lived = tokio::spawn(request_with_ttl::<ExpiringRequest>(
+        2,
+        Duration::from_millis(500),
+    ));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let fresh = tokio::spawn(ExpiringRequest::request(3));
+    tokio::task::yield_now().await;
+
+    println!("expired_requests: Expired request is skipped");
+    for _ in 0..2 {
+        let (payload, responder) = listener.next().await.unwrap();
+        assert_ne!(payload, 1);
+        responder.respond(payload * 10).unwrap();
+    }
+    assert!(matches!(expired.await.unwrap(), Err(RequestError::Expired)));
+    assert_eq!(long_lived.await.unwrap().unwrap(), 20);
+    assert_eq!(fresh.await.unwrap().unwrap(), 30);
+    assert_eq!(expired_count::<ExpiringRequest>(), 1);
+
+    println!("expired_requests: Request expires behind the slow handler");
+    let start = Instant::now();
+    let slow = tokio::spawn(ExpiringRequest::request(4));
+    let (payload, responder) = listener.next().await.unwrap();
+    assert_eq!(payload, 4);
+    let queued = tokio::spawn(ExpiringRequest::request(5));
+    let handler = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        responder.respond(40).unwrap();
+    });
+    assert!(matches!(queued.await.unwrap(), Err(RequestError::Expired)));
+    assert!(start.elapsed() < Duration::from_millis(500));
+
+    println!("expired_requests: Accepted request is handled after its ttl");
+    handler.await.unwrap();
+    assert_eq!(slow.await.unwrap().unwrap(), 40);
+    let fresh = tokio::spawn(ExpiringRequest::request(6));
+    let (payload, responder) = listener.next().await.unwrap();
+    assert_eq!(payload, 6);
+    responder.respond(60).unwrap();
+    assert_eq!(fresh.await.unwrap().unwrap(), 60);
+    assert_eq!(expired_count::<ExpiringRequest>(), 2);
+    listener.close().await;
+}
+
+crate::declare! {
+    request BatchedRequest(i32) -> i32 throws &'static str;
+}
+
+#[tokio::test]
+async fn batch_accept() {
+    println!("batch_accept: Queue requests before accepting");
+    let mut listener = listen::<BatchedRequest>().await.unwrap();
+    let requests: Vec<_> = (1..=5)
+        .map(|n| tokio::spawn(BatchedRequest::request(n)))
+        .collect();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    println!("batch_accept: Accept batches");
+    let accepted = listener
+        .accept_batch(3, |payloads| async move {
+            assert_eq!(payloads, [1, 2, 3]);
+            payloads
+                .into_iter()
+                .map(|n| match n {
+                    2 => Err("two"),
+                    n => Ok(n * 10),
+                })
+                .collect()
+        })
+        .await;
+    assert_eq!(accepted, 3);
+    // the request left without the response is not responded
+    let accepted = listener
+        .accept_batch(3, |payloads| async move {
+            assert_eq!(payloads, [4, 5]);
+            vec![Ok(40)]
+        })
+        .await;
+    assert_eq!(accepted, 2);
+
+    let mut replies = Vec::new();
+    for request in requests {
+        replies.push(request.await.unwrap());
+    }
+    assert_eq!(*replies[0].as_ref().unwrap(), 10);
+    assert!(matches!(replies[1], Err(RequestError::Handler("two"))));
+    assert_eq!(*replies[2].as_ref().unwrap(), 30);
+    assert_eq!(*replies[3].as_ref().unwrap(), 40);
+    assert!(matches!(replies[4], Err(RequestError::NotResponded)));
+    listener.close().await;
+}
+
+crate::declare! {
+    request[4] DroppedRequest(i32) -> i32;
+    request[4] DroppedSharedRequest(i32) -> i32;
+}
+
+#[tokio::test]
+async fn dropped_listeners() {
+    println!("dropped_listeners: Drop listener with queued request");
+    let listener = listen::<DroppedRequest>().await.unwrap();
+    let queued = tokio::spawn(DroppedRequest::request(1));
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    drop(listener);
+    let reply = tokio::time::timeout(Duration::from_millis(100), queued)
+        .await
+        .expect("queued request is not failed")
+        .unwrap();
+    assert!(matches!(reply, Err(RequestError::NotResponded)));
+
+    println!("dropped_listeners: Drop shared listener with queued request");
+    let dropped = listen_shared::<DroppedSharedRequest>(Strategy::RoundRobin)
+        .await
+        .unwrap();
+    let mut listener = listen_shared::<DroppedSharedRequest>(Strategy::RoundRobin)
+        .await
+        .unwrap();
+    let requests: Vec<_> = (1..=2)
+        .map(|n| tokio::spawn(DroppedSharedRequest::request(n)))
+        .collect();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    drop(dropped);
+    for _ in 0..2 {
+        let (payload, responder) = listener.next().await.unwrap();
+        responder.respond(payload * 10).unwrap();
+    }
+    for (request, expected) in requests.into_iter().zip([10, 20]) {
+        assert_eq!(request.await.unwrap().unwrap(), expected);
+    }
+    listener.close().await;
+}
+
+crate::declare! {
+    request[1] BusyRequest(i32) -> i32;
+}
+
+#[tokio::test]
+async fn gather_busy_listeners() {
+    let timeout = Duration::from_millis(50);
+    let mut listener = listen::<BusyRequest>().await.unwrap();
+
+    println!("gather_busy_listeners: Quorum is not sent to too few listeners");
+    assert!(matches!(
+        quorum::<BusyRequest>(1, 2, timeout).await,
+        Err(RequestError::NotListened(1))
+    ));
+    let received = tokio::time::timeout(Duration::from_millis(20), listener.next()).await;
+    assert!(received.is_err());
+
+    println!("gather_busy_listeners: Full listener is timed out");
+    let queued = tokio::spawn(BusyRequest::request(2));
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let replies = tokio::time::timeout(
+        Duration::from_millis(500),
+        gather::<BusyRequest>(3, timeout),
+    )
+    .await
+    .expect("gather is not timed out while sending");
+    assert_eq!(replies.len(), 1);
+    assert!(matches!(replies[0], Err(RequestError::TimedOut)));
+
+    let (payload, responder) = listener.next().await.unwrap();
+    assert_eq!(payload, 2);
+    responder.respond(20).unwrap();
+    assert_eq!(queued.await.unwrap().unwrap(), 20);
+    listener.close().await;
+}
@@ -0,0 +1,191 @@
+//! Retry policies
+//!
+//! Retrying of requests and notifications
+//! while there is no listener or subscriber
+
+use crate::common::random;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Policy of retrying requests and notifications
+///
+/// Used by [request_with_retry](crate::request::request_with_retry),
+/// [notify_with_retry](crate::notification::notify_with_retry)
+/// and for the types declared with the `retry` option
+///
+/// ## Example
+///
+/// ```rust
+/// use intercomm::retry::RetryPolicy;
+/// use std::time::Duration;
+///
+/// const POLICY: RetryPolicy = RetryPolicy::exponential(Duration::from_millis(10))
+///     .with_max_backoff(Duration::from_secs(1))
+///     .with_jitter()
+///     .with_max_attempts(10)
+///     .with_deadline(Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: u32,
+    jitter: bool,
+    max_attempts: Option<u32>,
+    deadline: Option<Duration>,
+}
+
+impl RetryPolicy {
+    /// Creates the policy that retries with the exponential backoff
+    /// starting from `initial_backoff` and doubled after each attempt
+    ///
+    /// By default the backoff is limited to 30 seconds
+    /// and the number of attempts and the overall time are unlimited
+    pub const fn exponential(initial_backoff: Duration) -> Self {
+        Self {
+            initial_backoff,
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2,
+            jitter: false,
+            max_attempts: None,
+            deadline: None,
+        }
+    }
+
+    /// Creates the policy that retries with the same backoff
+    pub const fn fixed(backoff: Duration) -> Self {
+        Self::exponential(backoff).with_multiplier(1)
+    }
+
+    /// Sets the multiplier of the backoff after each attempt
+    pub const fn with_multiplier(self, multiplier: u32) -> Self {
+        Self { multiplier, ..self }
+    }
+
+    /// Sets the limit of the backoff
+    pub const fn with_max_backoff(self, max_backoff: Duration) -> Self {
+        Self {
+            max_backoff,
+            ..self
+        }
+    }
+
+    /// Randomizes each backoff between its half and its full value
+    pub const fn with_jitter(self) -> Self {
+        Self {
+            jitter: true,
+            ..self
+        }
+    }
+
+    /// Sets the limit of the attempts including the first one
+    pub const fn with_max_attempts(self, max_attempts: u32) -> Self {
+        Self {
+            max_attempts: Some(max_attempts),
+            ..self
+        }
+    }
+
+    /// Sets the limit of the overall time of the retries
+    pub const fn with_deadline(self, deadline: Duration) -> Self {
+        Self {
+            deadline: Some(deadline),
+            ..self
+        }
+    }
+
+    /// Returns the backoff before the retry after the `attempt`
+    ///
+    /// Attempts are counted from 1
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let mut backoff = self.initial_backoff;
+        for _ in 1..attempt {
+            backoff = backoff.saturating_mul(self.multiplier);
+            if backoff >= self.max_backoff {
+                break;
+            }
+        }
+        let backoff = backoff.min(self.max_backoff);
+        if self.jitter {
+            let half = backoff / 2;
+            let nanos = half.as_nanos() as u64;
+            match nanos {
+                0 => backoff,
+                nanos => half + Duration::from_nanos(random() % (nanos + 1)),
+            }
+        } else {
+            backoff
+        }
+    }
+
+    pub(crate) fn start(&self) -> Retry<'_> {
+        Retry {
+            policy: self,
+            attempt: 0,
+            deadline: self.deadline.map(|deadline| Instant::now() + deadline),
+        }
+    }
+}
+
+/// State of the retries by the policy
+pub(crate) struct Retry<'a> {
+    policy: &'a RetryPolicy,
+    attempt: u32,
+    deadline: Option<Instant>,
+}
+
+impl Retry<'_> {
+    /// Returns the backoff before the next attempt
+    /// or None if the attempts are exhausted
+    pub(crate) fn next_backoff(&mut self) -> Option<Duration> {
+        self.attempt += 1;
+        if let Some(max_attempts) = self.policy.max_attempts {
+            if self.attempt >= max_attempts {
+                return None;
+            }
+        }
+        let backoff = self.policy.backoff(self.attempt);
+        match self.deadline {
+            Some(deadline) if Instant::now() + backoff > deadline => None,
+            _ => Some(backoff),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exponential_backoff() {
+        let policy = RetryPolicy::exponential(Duration::from_millis(10))
+            .with_max_backoff(Duration::from_millis(50));
+        assert_eq!(policy.backoff(1), Duration::from_millis(10));
+        assert_eq!(policy.backoff(2), Duration::from_millis(20));
+        assert_eq!(policy.backoff(3), Duration::from_millis(40));
+        assert_eq!(policy.backoff(4), Duration::from_millis(50));
+        assert_eq!(policy.backoff(100), Duration::from_millis(50));
+
+        let policy = RetryPolicy::fixed(Duration::from_millis(10));
+        assert_eq!(policy.backoff(5), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn jitter_backoff() {
+        let policy = RetryPolicy::exponential(Duration::from_millis(10)).with_jitter();
+        for _ in 0..100 {
+            let backoff = policy.backoff(2);
+            assert!(backoff >= Duration::from_millis(10));
+            assert!(backoff <= Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn max_attempts() {
+        let policy = RetryPolicy::fixed(Duration::from_millis(1)).with_max_attempts(3);
+        let mut retry = policy.start();
+        assert!(retry.next_backoff().is_some());
+        assert!(retry.next_backoff().is_some());
+        assert!(retry.next_backoff().is_none());
+    }
+}
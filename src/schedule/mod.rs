@@ -0,0 +1,90 @@
+//! Scheduling
+//!
+//! Delayed and recurring notifications and broadcasts,
+//! see [notification::notify_at](crate::notification::notify_at)
+//! and [broadcast::notify_at](crate::broadcast::notify_at)
+//!
+//! Every schedule is run by its own task on the runtime it is made on,
+//! it stops when that runtime is shut down
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::Notify, time::Instant};
+
+#[cfg(test)]
+mod test;
+
+/// Handle of the scheduled notification
+///
+/// Dropping the handle does not cancel the schedule
+#[derive(Debug, Clone)]
+pub struct ScheduleHandle {
+    cancel: Arc<Cancel>,
+}
+
+#[derive(Debug)]
+struct Cancel {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl ScheduleHandle {
+    /// Cancels the schedule,
+    /// the notifications that are already sent are not affected
+    pub fn cancel(&self) {
+        self.cancel.cancelled.store(true, Ordering::Release);
+        // the permit is kept if the task is sending now
+        self.cancel.notify.notify_one();
+    }
+
+    /// Returns true if the schedule is cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.cancelled.load(Ordering::Acquire)
+    }
+}
+
+/// Sender of the scheduled payload
+pub(crate) type Job = Box<dyn FnMut() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+/// Schedules the `job` at the instant, and then every `period` if it is set
+///
+/// The job is run by a task spawned on the current runtime,
+/// the next run waits for the previous one to finish
+///
+/// # Panics
+///
+/// Panics if called outside of the tokio runtime
+pub(crate) fn schedule(at: Instant, period: Option<Duration>, mut job: Job) -> ScheduleHandle {
+    let cancel = Arc::new(Cancel {
+        cancelled: AtomicBool::new(false),
+        notify: Notify::new(),
+    });
+    let handle = ScheduleHandle {
+        cancel: cancel.clone(),
+    };
+    tokio::spawn(async move {
+        let mut at = at;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(at) => {}
+                _ = cancel.notify.notified() => return,
+            }
+            if cancel.cancelled.load(Ordering::Acquire) {
+                return;
+            }
+            job().await;
+            match period {
+                Some(period) => at = (at + period).max(Instant::now()),
+                None => return,
+            }
+        }
+    });
+    handle
+}
@@ -0,0 +1,97 @@
+use crate::{
+    broadcast::{self, Broadcast},
+    notification::{self, Notification},
+};
+use std::time::Duration;
+use tokio::time::Instant;
+
+struct Delayed;
+
+impl Notification for Delayed {
+    type Payload = i32;
+    const BUFFER_SIZE: usize = 0;
+    const DEBUG_NAME: &'static str = "Delayed";
+}
+
+struct Recurring;
+
+impl Broadcast for Recurring {
+    type Payload = i32;
+    const BUFFER_SIZE: usize = 8;
+    const DEBUG_NAME: &'static str = "Recurring";
+}
+
+#[tokio::test]
+async fn scheduled_notifications() {
+    tokio::time::pause();
+    println!("scheduled_notifications: Schedule notifications");
+    let mut subscription = notification::subscribe::<Delayed>().await.unwrap();
+    let start = Instant::now();
+    notification::notify_after::<Delayed>(Duration::from_millis(100), 1);
+    notification::notify_at::<Delayed>(start + Duration::from_millis(50), 2);
+    let cancelled = notification::notify_after::<Delayed>(Duration::from_millis(70), 3);
+    cancelled.cancel();
+    assert!(cancelled.is_cancelled());
+
+    assert_eq!(subscription.recv().await, 2);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert_eq!(subscription.recv().await, 1);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    let cancelled = tokio::time::timeout(Duration::from_millis(100), subscription.recv()).await;
+    assert!(cancelled.is_err());
+    subscription.close().await;
+
+    println!("scheduled_notifications: Schedule recurring broadcast");
+    let mut subscription = broadcast::subscribe::<Recurring>().await;
+    let mut counter = 0;
+    let recurring = broadcast::notify_every::<Recurring, _>(Duration::from_millis(30), move || {
+        counter += 1;
+        counter
+    });
+    for expected in 1..=3 {
+        assert_eq!(subscription.recv().await, expected);
+    }
+    recurring.cancel();
+    let cancelled = tokio::time::timeout(Duration::from_millis(100), subscription.recv()).await;
+    assert!(cancelled.is_err());
+    subscription.close().await;
+}
+
+struct Rescheduled;
+
+impl Notification for Rescheduled {
+    type Payload = i32;
+    const BUFFER_SIZE: usize = 0;
+    const DEBUG_NAME: &'static str = "Rescheduled";
+}
+
+fn new_runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn runtimes() {
+    println!("runtimes: Schedule on the runtime that is shut down");
+    let runtime = new_runtime();
+    runtime.block_on(async {
+        notification::notify_after::<Rescheduled>(Duration::from_secs(1), 1);
+    });
+    drop(runtime);
+
+    println!("runtimes: Schedule on another runtime");
+    let idle = new_runtime();
+    idle.block_on(async {
+        notification::notify_after::<Rescheduled>(Duration::from_secs(1), 2);
+    });
+    let runtime = new_runtime();
+    runtime.block_on(async {
+        let mut subscription = notification::subscribe::<Rescheduled>().await.unwrap();
+        notification::notify_after::<Rescheduled>(Duration::from_millis(50), 3);
+        let received = tokio::time::timeout(Duration::from_millis(500), subscription.recv()).await;
+        assert_eq!(received, Ok(3));
+        subscription.close().await;
+    });
+}
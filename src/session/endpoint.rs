@@ -0,0 +1,104 @@
+use futures_core::Stream;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc::{Receiver, Sender};
+
+/// One side of the session
+///
+/// Sends messages of `Tx` type and receives messages of `Rx` type,
+/// messages are received in the same order as they were sent
+pub struct Endpoint<Tx, Rx> {
+    sink: SessionSink<Tx>,
+    stream: SessionStream<Rx>,
+}
+
+/// Sending half of the [Endpoint]
+pub struct SessionSink<T> {
+    sender: Sender<T>,
+}
+
+/// Receiving half of the [Endpoint]
+pub struct SessionStream<T> {
+    receiver: Receiver<T>,
+}
+
+impl<Tx, Rx> Endpoint<Tx, Rx> {
+    pub(crate) fn new(sender: Sender<Tx>, receiver: Receiver<Rx>) -> Self {
+        Self {
+            sink: SessionSink { sender },
+            stream: SessionStream { receiver },
+        }
+    }
+
+    /// Sends a message to the other side
+    ///
+    /// Returns the message back if the other side is gone
+    pub async fn send(&self, message: Tx) -> Result<(), Tx> {
+        self.sink.send(message).await
+    }
+
+    /// Receives the next message from the other side
+    ///
+    /// Returns None if the other side is gone
+    pub async fn recv(&mut self) -> Option<Rx> {
+        self.stream.recv().await
+    }
+
+    /// Checks if the other side has stopped receiving messages
+    pub fn is_closed(&self) -> bool {
+        self.sink.is_closed()
+    }
+
+    /// Splits the endpoint into the sending and the receiving halves
+    pub fn split(self) -> (SessionSink<Tx>, SessionStream<Rx>) {
+        (self.sink, self.stream)
+    }
+}
+
+impl<Tx, Rx> Stream for Endpoint<Tx, Rx> {
+    type Item = Rx;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.stream).poll_next(cx)
+    }
+}
+
+impl<T> SessionSink<T> {
+    /// Sends a message to the other side
+    ///
+    /// Waits while the message buffer is full
+    ///
+    /// Returns the message back if the other side is gone
+    pub async fn send(&self, message: T) -> Result<(), T> {
+        self.sender.send(message).await.map_err(|e| e.0)
+    }
+
+    /// Checks if the other side has stopped receiving messages
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Waits until the other side stops receiving messages
+    pub async fn closed(&self) {
+        self.sender.closed().await
+    }
+}
+
+impl<T> SessionStream<T> {
+    /// Receives the next message from the other side
+    ///
+    /// Returns None if the other side is gone
+    pub async fn recv(&mut self) -> Option<T> {
+        self.receiver.recv().await
+    }
+}
+
+impl<T> Stream for SessionStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
@@ -0,0 +1,78 @@
+use super::{Endpoint, Session, CHANNELS};
+use crate::common::UntypedBox;
+use std::{future::Future, mem};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+/// Session listener
+pub struct Listener<S: Session> {
+    receiver: Option<UnboundedReceiver<Endpoint<S::Out, S::In>>>,
+}
+
+/// Listen to session
+///
+/// Returns None if session is already listened
+pub async fn listen<S: Session>() -> Option<Listener<S>> {
+    let id = id!(S);
+    let mut channels = CHANNELS.write().await;
+    if channels.contains_key(&id) {
+        return None;
+    }
+    let (tx, rx) = unbounded_channel::<Endpoint<S::Out, S::In>>();
+    channels.insert(id, UntypedBox::new(tx));
+    Some(Listener { receiver: Some(rx) })
+}
+
+impl<S: Session> Listener<S> {
+    /// Accepts next session for this Listener
+    ///
+    /// The session is finished when the future returned by `f` completes
+    pub async fn accept<F, Fut>(&mut self, f: F)
+    where
+        F: FnOnce(Endpoint<S::Out, S::In>) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let endpoint = match self.next().await {
+            Some(endpoint) => endpoint,
+            None => unreachable!(),
+        };
+        f(endpoint).await
+    }
+
+    /// Accepts next session for this Listener
+    ///
+    /// Returns the listener side of the session
+    /// or None if the listener is closed
+    pub async fn next(&mut self) -> Option<Endpoint<S::Out, S::In>> {
+        match &mut self.receiver {
+            Some(rx) => rx.recv().await,
+            None => None,
+        }
+    }
+
+    /// Closes the listener
+    ///
+    /// Closing the listener with this method
+    /// is preferable for performance reasons
+    pub async fn close(mut self) {
+        match mem::take(&mut self.receiver) {
+            Some(mut rx) => rx.close(),
+            None => unreachable!(),
+        }
+        CHANNELS.write().await.remove(&id!(S));
+    }
+}
+
+impl<S: Session> Drop for Listener<S> {
+    fn drop(&mut self) {
+        match &mut self.receiver {
+            Some(rx) => rx.close(),
+            None => return,
+        }
+        CHANNELS.remove_when_possible(id!(S));
+        #[cfg(debug_assertions)]
+        eprintln!(
+            "Listener for {} closed in slow manner! Use close method for optimize it",
+            S::DEBUG_NAME
+        );
+    }
+}
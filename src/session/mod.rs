@@ -0,0 +1,69 @@
+//! Sessions
+//!
+//! Long-lived bidirectional communications
+
+use crate::common::StaticTypeMap;
+use tokio::sync::mpsc::{self, error::SendError, UnboundedSender};
+
+mod endpoint;
+mod listener;
+
+#[cfg(test)]
+mod test;
+
+pub use endpoint::*;
+pub use listener::*;
+
+static CHANNELS: StaticTypeMap = StaticTypeMap::new();
+
+/// A bidirectional session between the initiator and the listener
+pub trait Session: Sized + 'static {
+    /// The number of messages in each direction
+    /// that can be sent without waiting for the other side
+    ///
+    /// It must be at least 1
+    const BUFFER_SIZE: usize;
+
+    /// Session name in debug messages
+    const DEBUG_NAME: &'static str;
+
+    /// Message data type that will be sended from initiator to listener
+    type In: Send;
+
+    /// Message data type that will be sended from listener to initiator
+    type Out: Send;
+}
+
+/// This enumeration is the list of the possible error outcomes for the
+/// [open](crate::session::open) fn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SessionError {
+    /// Has not listener of this session
+    NotListened,
+    /// Internal session channel is closed
+    SendError,
+}
+
+/// Opens a session with the [Listener](crate::session::Listener)
+///
+/// Returns the initiator side of the session
+pub async fn open<S: Session>() -> Result<Endpoint<S::In, S::Out>, SessionError> {
+    let id = id!(S);
+    let channels = CHANNELS.read().await;
+    let sender = match channels.get(&id) {
+        Some(sender) => sender,
+        None => return Err(SessionError::NotListened),
+    };
+    let (in_tx, in_rx) = mpsc::channel(S::BUFFER_SIZE);
+    let (out_tx, out_rx) = mpsc::channel(S::BUFFER_SIZE);
+    let sender: &UnboundedSender<Endpoint<S::Out, S::In>> = unsafe { sender.get_ref() };
+    sender.send(Endpoint::new(out_tx, in_rx))?;
+    Ok(Endpoint::new(in_tx, out_rx))
+}
+
+impl<T> From<SendError<T>> for SessionError {
+    fn from(_: SendError<T>) -> Self {
+        SessionError::SendError
+    }
+}
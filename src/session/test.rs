@@ -0,0 +1,63 @@
+use super::*;
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+struct EchoSession;
+
+impl Session for EchoSession {
+    type In = String;
+    type Out = String;
+    const BUFFER_SIZE: usize = 2;
+    const DEBUG_NAME: &'static str = "EchoSession";
+}
+
+async fn echo_listener(session_count: usize, ready: Arc<Notify>) {
+    println!("Listen: Echo");
+    let mut listener = listen::<EchoSession>().await.unwrap();
+    ready.notify_one();
+    for i in 0..session_count {
+        println!("Accept: Echo #{}", i);
+        listener
+            .accept(|mut endpoint| async move {
+                while let Some(message) = endpoint.recv().await {
+                    if endpoint.send(message.to_uppercase()).await.is_err() {
+                        return;
+                    }
+                }
+            })
+            .await;
+    }
+    listener.close().await;
+}
+
+#[tokio::test]
+async fn echo_session() {
+    let ready = Arc::new(Notify::new());
+    println!("echo_session: Start listener");
+    let l = tokio::spawn(echo_listener(2, ready.clone()));
+    ready.notified().await;
+
+    println!("echo_session: Talk in session #1");
+    let mut endpoint = open::<EchoSession>().await.unwrap();
+    for message in ["a", "b", "c"] {
+        endpoint.send(message.to_string()).await.unwrap();
+        assert_eq!(endpoint.recv().await.unwrap(), message.to_uppercase());
+    }
+    drop(endpoint);
+
+    println!("echo_session: Talk in session #2 with split endpoint");
+    let (sink, mut stream) = open::<EchoSession>().await.unwrap().split();
+    sink.send("x".to_string()).await.unwrap();
+    sink.send("y".to_string()).await.unwrap();
+    assert_eq!(stream.recv().await.unwrap(), "X");
+    assert_eq!(stream.recv().await.unwrap(), "Y");
+    drop(sink);
+    assert_eq!(stream.recv().await, None);
+
+    println!("echo_session: Join listener");
+    l.await.unwrap();
+    assert_eq!(
+        open::<EchoSession>().await.err(),
+        Some(SessionError::NotListened)
+    );
+}
@@ -0,0 +1,101 @@
+use super::{ResponseSink, StreamRequest, StreamRequestPair, CHANNELS};
+use crate::common::UntypedBox;
+use std::{future::Future, mem};
+use tokio::sync::mpsc::{channel, unbounded_channel, Receiver, UnboundedReceiver};
+
+/// Stream request listener
+pub struct Listener<S: StreamRequest> {
+    receiver: RequestReceiver<S>,
+}
+
+/// Listen to stream request
+///
+/// Returns None if stream request is already listened
+pub async fn listen<S: StreamRequest>() -> Option<Listener<S>> {
+    let id = id!(S);
+    let mut channels = CHANNELS.write().await;
+    if channels.contains_key(&id) {
+        return None;
+    }
+    let (sender, receiver) = if S::BUFFER_SIZE == 0 {
+        let (tx, rx) = unbounded_channel();
+        let tx = UntypedBox::new(tx);
+        let rx = RequestReceiver::Unbounded(rx);
+        (tx, rx)
+    } else {
+        let (tx, rx) = channel(S::BUFFER_SIZE);
+        let tx = UntypedBox::new(tx);
+        let rx = RequestReceiver::Bounded(rx);
+        (tx, rx)
+    };
+    channels.insert(id, sender);
+    Some(Listener { receiver })
+}
+
+enum RequestReceiver<S: StreamRequest> {
+    Bounded(Receiver<StreamRequestPair<S>>),
+    Unbounded(UnboundedReceiver<StreamRequestPair<S>>),
+    Closed,
+}
+
+impl<S: StreamRequest> Listener<S> {
+    /// Accepts next request for this Listener
+    ///
+    /// The response is finished when the future returned by `f` completes
+    pub async fn accept<F, Fut>(&mut self, f: F)
+    where
+        F: FnOnce(S::Payload, ResponseSink<S>) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let (payload, sink) = match self.next().await {
+            Some(request) => request,
+            None => unreachable!(),
+        };
+        f(payload, sink).await
+    }
+
+    /// Accepts next request for this Listener
+    /// without responding to it
+    ///
+    /// The response is finished when the returned [ResponseSink] is dropped
+    ///
+    /// Returns None if the listener is closed
+    pub async fn next(&mut self) -> Option<(S::Payload, ResponseSink<S>)> {
+        let request_pair = match &mut self.receiver {
+            RequestReceiver::Bounded(rx) => rx.recv().await,
+            RequestReceiver::Unbounded(rx) => rx.recv().await,
+            RequestReceiver::Closed => None,
+        };
+        request_pair.map(|StreamRequestPair { payload, sink }| (payload, ResponseSink::new(sink)))
+    }
+
+    /// Closes the listener
+    ///
+    /// Closing the listener with this method
+    /// is preferable for performance reasons
+    pub async fn close(mut self) {
+        let receiver = mem::replace(&mut self.receiver, RequestReceiver::Closed);
+        match receiver {
+            RequestReceiver::Bounded(mut rx) => rx.close(),
+            RequestReceiver::Unbounded(mut rx) => rx.close(),
+            _ => unreachable!(),
+        }
+        CHANNELS.write().await.remove(&id!(S));
+    }
+}
+
+impl<S: StreamRequest> Drop for Listener<S> {
+    fn drop(&mut self) {
+        match &mut self.receiver {
+            RequestReceiver::Bounded(rx) => rx.close(),
+            RequestReceiver::Unbounded(rx) => rx.close(),
+            RequestReceiver::Closed => return,
+        }
+        CHANNELS.remove_when_possible(id!(S));
+        #[cfg(debug_assertions)]
+        eprintln!(
+            "Listener for {} closed in slow manner! Use close method for optimize it",
+            S::DEBUG_NAME
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Stream requests
+//!
+//! Request-response communications with a stream of response items
+
+use crate::common::StaticTypeMap;
+use tokio::sync::mpsc::{self, error::SendError, Sender, UnboundedSender};
+
+mod listener;
+mod stream;
+
+#[cfg(test)]
+mod test;
+
+pub use listener::*;
+pub use stream::*;
+
+static CHANNELS: StaticTypeMap = StaticTypeMap::new();
+
+/// A request with a stream of response items
+pub trait StreamRequest: Sized + 'static {
+    /// The number of requests
+    /// that can be sent without waiting for the listener
+    ///
+    /// Set to 0 for unlimited buffer
+    const BUFFER_SIZE: usize;
+
+    /// The number of response items
+    /// that can be sent without waiting for the requester
+    ///
+    /// It must be at least 1
+    const STREAM_BUFFER_SIZE: usize;
+
+    /// Request name in debug messages
+    const DEBUG_NAME: &'static str;
+
+    /// Payload data type that will be sended with this request
+    type Payload: Send;
+
+    /// Response item data type that will be streamed from listener
+    type Item: Send;
+}
+
+/// This enumeration is the list of the possible error outcomes for the
+/// [request](crate::stream_request::request) fn
+#[non_exhaustive]
+pub enum StreamRequestError<S: StreamRequest> {
+    /// Has not listener of this request
+    NotListened(S::Payload),
+    /// Internal request channel is closed
+    SendError(S::Payload),
+}
+
+/// Sends a payload to the [Listener](crate::stream_request::Listener)
+///
+/// Returns the stream of the response items
+pub async fn request<S: StreamRequest>(
+    payload: S::Payload,
+) -> Result<ResponseStream<S>, StreamRequestError<S>> {
+    let id = id!(S);
+    let channels = CHANNELS.read().await;
+    let sender = match channels.get(&id) {
+        Some(sender) => sender,
+        None => return Err(StreamRequestError::NotListened(payload)),
+    };
+    let (tx, rx) = mpsc::channel(S::STREAM_BUFFER_SIZE);
+    let request_pair = StreamRequestPair::<S> { payload, sink: tx };
+    if S::BUFFER_SIZE == 0 {
+        let sender: &UnboundedSender<_> = unsafe { sender.get_ref() };
+        sender.send(request_pair)?;
+    } else {
+        let sender: &Sender<_> = unsafe { sender.get_ref() };
+        sender.send(request_pair).await?;
+    }
+    Ok(ResponseStream::new(rx))
+}
+
+struct StreamRequestPair<S: StreamRequest> {
+    payload: S::Payload,
+    sink: Sender<S::Item>,
+}
+
+impl<S: StreamRequest> From<SendError<StreamRequestPair<S>>> for StreamRequestError<S> {
+    fn from(e: SendError<StreamRequestPair<S>>) -> Self {
+        let StreamRequestPair { payload, .. } = e.0;
+        StreamRequestError::SendError(payload)
+    }
+}
+
+impl<S: StreamRequest> std::fmt::Debug for StreamRequestError<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamRequestError::NotListened(_) => {
+                write!(f, "StreamRequestError in {}: NotListened", S::DEBUG_NAME)?;
+            }
+            StreamRequestError::SendError(_) => {
+                write!(f, "StreamRequestError in {}: SendError", S::DEBUG_NAME)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use super::StreamRequest;
+use futures_core::Stream;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc::{Receiver, Sender};
+
+/// Stream of the response items for the sent request
+///
+/// Dropping the stream stops the listener's [ResponseSink]
+pub struct ResponseStream<S: StreamRequest> {
+    receiver: Receiver<S::Item>,
+}
+
+/// Sink of the response items for the accepted request
+pub struct ResponseSink<S: StreamRequest> {
+    sender: Sender<S::Item>,
+}
+
+impl<S: StreamRequest> ResponseStream<S> {
+    pub(crate) fn new(receiver: Receiver<S::Item>) -> Self {
+        Self { receiver }
+    }
+
+    /// Receives the next response item
+    ///
+    /// Returns None when the listener has finished the response
+    pub async fn recv(&mut self) -> Option<S::Item> {
+        self.receiver.recv().await
+    }
+}
+
+impl<S: StreamRequest> Stream for ResponseStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl<S: StreamRequest> ResponseSink<S> {
+    pub(crate) fn new(sender: Sender<S::Item>) -> Self {
+        Self { sender }
+    }
+
+    /// Sends the response item to the requester
+    ///
+    /// Waits while the stream buffer is full
+    ///
+    /// Returns the item back if the requester has dropped the stream
+    pub async fn send(&self, item: S::Item) -> Result<(), S::Item> {
+        self.sender.send(item).await.map_err(|e| e.0)
+    }
+
+    /// Checks if the requester has dropped the stream
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Waits until the requester drops the stream
+    pub async fn closed(&self) {
+        self.sender.closed().await
+    }
+}
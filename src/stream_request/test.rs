@@ -0,0 +1,62 @@
+use super::*;
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+struct RangeRequest;
+
+impl StreamRequest for RangeRequest {
+    type Payload = i32;
+    type Item = i32;
+    const BUFFER_SIZE: usize = 1;
+    const STREAM_BUFFER_SIZE: usize = 2;
+    const DEBUG_NAME: &'static str = "RangeRequest";
+}
+
+async fn range_listener(request_count: usize, ready: Arc<Notify>, finished: Arc<Notify>) {
+    println!("Listen: Range");
+    let mut listener = listen::<RangeRequest>().await.unwrap();
+    ready.notify_one();
+    for i in 0..request_count {
+        println!("Accept: Range #{}", i);
+        listener
+            .accept(|n, sink| async move {
+                for item in 0..n {
+                    if sink.send(item).await.is_err() {
+                        println!("Accept: Range stream dropped on {}", item);
+                        return;
+                    }
+                }
+            })
+            .await;
+        finished.notify_one();
+    }
+    listener.close().await;
+}
+
+#[tokio::test]
+async fn range_stream() {
+    let ready = Arc::new(Notify::new());
+    let finished = Arc::new(Notify::new());
+    println!("range_stream: Start listener");
+    let l = tokio::spawn(range_listener(2, ready.clone(), finished.clone()));
+    ready.notified().await;
+
+    println!("range_stream: Receive whole stream");
+    let mut stream = request::<RangeRequest>(5).await.unwrap();
+    let mut items = Vec::new();
+    while let Some(item) = stream.recv().await {
+        items.push(item);
+    }
+    assert_eq!(items, vec![0, 1, 2, 3, 4]);
+    finished.notified().await;
+
+    println!("range_stream: Drop stream early");
+    let mut stream = request::<RangeRequest>(1000).await.unwrap();
+    assert_eq!(stream.recv().await, Some(0));
+    drop(stream);
+    finished.notified().await;
+
+    println!("range_stream: Join listener");
+    l.await.unwrap();
+    assert!(request::<RangeRequest>(1).await.is_err());
+}